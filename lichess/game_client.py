@@ -1,10 +1,22 @@
 import threading
 import random
+import datetime
 import chess
 import rc2d2
 
+
+def to_millis(value):
+    """berserk converts clock times to datetimes relative to the epoch"""
+    if isinstance(value, datetime.datetime):
+        if value.tzinfo is not None:
+            value = value.astimezone(datetime.timezone.utc).replace(tzinfo=None)
+        value = value - datetime.datetime(1970, 1, 1)
+    if isinstance(value, datetime.timedelta):
+        return int(value.total_seconds() * 1000)
+    return int(value)
+
 class Game(threading.Thread):
-    def __init__(self, client, game_id, bot_id, depth, **kwargs):
+    def __init__(self, client, game_id, bot_id, depth, threads=1, **kwargs):
         super().__init__(**kwargs)
         self.bot_id = bot_id
         self.game_id = game_id
@@ -12,8 +24,10 @@ class Game(threading.Thread):
         self.stream = client.bots.stream_game_state(game_id)
         self.current_state = next(self.stream)
         self.depth = depth
+        self.engine = rc2d2.Engine(depth=depth, threads=threads)
 
         self.is_white = True
+        self.initial_fen = None
 
     def run(self):
         if self.current_state:
@@ -22,6 +36,10 @@ class Game(threading.Thread):
             except KeyError:
                 self.is_white = False
 
+            fen = self.current_state.get('initialFen', 'startpos')
+            if fen != 'startpos':
+                self.initial_fen = fen
+
             state = self.current_state['state']
             self.handle_state_change(state)
 
@@ -35,11 +53,20 @@ class Game(threading.Thread):
     
     def handle_state_change(self, game_state):
         all_moves = game_state['moves']
-        num_moves = len(all_moves.split(' '))
-        whites_turn = num_moves % 2 == 0
-        if whites_turn == self.is_white or (self.is_white and len(all_moves) == 0):
-            move = rc2d2.find_best_move(all_moves, self.depth)
+        self.engine.set_position(fen=self.initial_fen, moves=all_moves)
+        whites_turn = self.engine.fen().split(' ')[1] == 'w'
+        if whites_turn == self.is_white:
+            if 'wtime' in game_state and 'btime' in game_state:
+                result = self.engine.search(
+                    wtime=to_millis(game_state['wtime']),
+                    btime=to_millis(game_state['btime']),
+                    winc=to_millis(game_state.get('winc', 0)),
+                    binc=to_millis(game_state.get('binc', 0)),
+                )
+            else:
+                result = self.engine.search()
+            print(self.game_id, result)
             try: 
-                self.client.bots.make_move(self.game_id, move)
+                self.client.bots.make_move(self.game_id, result['move'])
             except TypeError:
                 pass
@@ -4,18 +4,25 @@ from game_client import Game
 import argparse
 parser = argparse.ArgumentParser(description="Client for rc2d2")
 parser.add_argument("--depth", default=4, help="Sets the search depth")
+parser.add_argument("--max-games", default=4, help="Number of games played at the same time")
+parser.add_argument("--threads", default=1, help="Search threads used by every game")
 args = parser.parse_args() 
 
 token = os.getenv('API_TOKEN')
 bot_id = os.getenv('BOT_ID')
 DEPTH = int(args.depth)
+MAX_GAMES = int(args.max_games)
+THREADS = int(args.threads)
 
 session = berserk.TokenSession(token)
 client = berserk.Client(session)
 
-acceptChallenge = True
+# every game runs on its own thread, the engine releases the GIL while searching
+games = {}
 
 for event in client.bots.stream_incoming_events():
+    games = {game_id: game for game_id, game in games.items() if game.is_alive()}
+
     if event['type'] == 'challenge':
         game_id = event['challenge']['id']
         challenge = event['challenge']
@@ -23,16 +30,15 @@ for event in client.bots.stream_incoming_events():
         if challenge['challenger']['id'] == bot_id:
             continue
 
-        if acceptChallenge:
+        if len(games) < MAX_GAMES:
             client.bots.accept_challenge(game_id)
-            acceptChallenge = False
         else:
             client.bots.decline_challenge(game_id)
 
     elif event['type'] == 'gameStart':
         game_id = event['game']['id']
-        game = Game(client, game_id, bot_id, DEPTH)
-        game.run()
+        game = Game(client, game_id, bot_id, DEPTH, threads=THREADS, daemon=True)
+        games[game_id] = game
+        game.start()
     elif event['type'] == 'gameFinish':
-        acceptChallenge = True
-
+        games.pop(event['game']['id'], None)
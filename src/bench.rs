@@ -0,0 +1,80 @@
+use pleco::Board;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::eval;
+use crate::search::{self, SearchParams};
+use crate::timeman::SearchLimits;
+use crate::tt::TranspositionTable;
+
+pub const DEFAULT_DEPTH: u8 = 5;
+
+/// A fixed set of positions from all phases of the game. The total node count of a
+/// search of these positions to a fixed depth shows how changes to the search affect
+/// the size of the tree, and changes when the search behaves differently.
+const POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4Pp2/1BNP4/PPP2PPP/3R1RK1 w - - 2 14",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
+];
+
+/// Searches every bench position to `depth` with an empty table and returns the total
+/// number of nodes searched.
+pub fn run(depth: u8, print: bool) -> u64 {
+    let start = Instant::now();
+    let mut tt = TranspositionTable::new(16);
+    let mut total = 0;
+    for (i, fen) in POSITIONS.iter().enumerate() {
+        let board = Board::from_fen(fen).unwrap();
+        tt.clear();
+        let result = search::iterative_deepening(
+            &board,
+            &SearchLimits::depth(depth),
+            &mut tt,
+            eval::eval,
+            &SearchParams::default(),
+            Arc::new(AtomicBool::new(false)),
+            |_| {},
+        );
+        if print {
+            println!(
+                "position {}: bestmove {} nodes {}",
+                i + 1,
+                result.best_move.stringify(),
+                result.nodes
+            );
+        }
+        total += result.nodes;
+    }
+
+    if print {
+        let elapsed = start.elapsed().as_millis() as u64;
+        println!("time  {} ms", elapsed);
+        println!("nodes {}", total);
+        println!("nps   {}", total * 1000 / elapsed.max(1));
+    }
+    return total;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_are_valid() {
+        for fen in POSITIONS.iter() {
+            assert!(Board::from_fen(fen).is_ok(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        assert_eq!(run(2, false), run(2, false));
+    }
+}
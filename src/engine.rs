@@ -0,0 +1,282 @@
+use pleco::Board;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::eval;
+use crate::position::{self, PositionError};
+use crate::search::{self, SearchParams, SearchResult};
+use crate::timeman::SearchLimits;
+use crate::tt::{self, TranspositionTable};
+
+pub const DEFAULT_DEPTH: u8 = 4;
+
+/// A board together with the search state that should survive between moves of a game.
+pub struct Engine {
+    board: Board,
+    tt: TranspositionTable,
+    /// Depth used when a search is started without any limits.
+    pub depth: u8,
+    pub params: SearchParams,
+}
+
+impl Engine {
+    pub fn new(hash_size: usize) -> Engine {
+        return Engine {
+            board: Board::start_pos(),
+            tt: TranspositionTable::new(hash_size),
+            depth: DEFAULT_DEPTH,
+            params: SearchParams::default(),
+        };
+    }
+
+    // the UCI binary never reads the board back
+    #[allow(dead_code)]
+    pub fn board(&self) -> &Board {
+        return &self.board;
+    }
+
+    /// Sets up the position from a FEN, or the start position when `fen` is `None`,
+    /// followed by `moves` in UCI notation. Leaves the board untouched if the FEN or any
+    /// of the moves is invalid.
+    pub fn set_position(&mut self, fen: Option<&str>, moves: &[&str]) -> Result<(), PositionError> {
+        self.board = position::setup(fen, moves)?;
+        return Ok(());
+    }
+
+    /// Plays a move in UCI notation. Only the Python module plays moves one by one.
+    #[allow(dead_code)]
+    pub fn push(&mut self, mv: &str) -> Result<(), PositionError> {
+        return position::play(&mut self.board, mv);
+    }
+
+    /// Takes back the last move played since the position was set up.
+    #[allow(dead_code)]
+    pub fn pop(&mut self) -> Option<String> {
+        if self.board.ply() == 0 {
+            return None;
+        }
+        let mv = self.board.last_move()?;
+        self.board.undo_move();
+        return Some(mv.stringify());
+    }
+
+    pub fn new_game(&mut self) {
+        self.board = Board::start_pos();
+        self.tt.clear();
+    }
+
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.tt.resize(size_mb.clamp(1, tt::MAX_SIZE_MB));
+    }
+
+    /// Sets an option by its case insensitive UCI name, clamping the value to the range
+    /// of the option. Check options take 0 or 1. Returns false for unknown names.
+    pub fn set_option(&mut self, name: &str, value: i64) -> bool {
+        if name.eq_ignore_ascii_case("hash") {
+            self.set_hash_size(value.max(1) as usize);
+        } else if name.eq_ignore_ascii_case("depth") {
+            self.depth = value.clamp(1, search::MAX_DEPTH as i64) as u8;
+        } else if name.eq_ignore_ascii_case("contempt") {
+            let max = search::MAX_CONTEMPT as i64;
+            self.params.contempt = value.clamp(-max, max) as i32;
+        } else if name.eq_ignore_ascii_case("threads") {
+            self.params.threads = value.clamp(1, search::MAX_THREADS as i64) as usize;
+        } else if name.eq_ignore_ascii_case("quiescencechecks") {
+            self.params.quiescence_checks = value != 0;
+        } else if self.params.tunable(name).is_some() {
+            self.params.set_tunable(name, value);
+        } else {
+            return false;
+        }
+        return true;
+    }
+
+    /// Searches the current position, setting `stop` aborts the search early. `report`
+    /// is called after every completed iteration.
+    pub fn search<F: FnMut(&SearchResult)>(
+        &mut self,
+        limits: &SearchLimits,
+        stop: Arc<AtomicBool>,
+        report: F,
+    ) -> Result<SearchResult, PositionError> {
+        if self.board.generate_moves().is_empty() {
+            return Err(PositionError::NoPosition);
+        }
+        let mut limits = limits.clone();
+        if limits.depth.is_none() && !limits.is_timed(self.board.turn()) && !limits.infinite {
+            limits.depth = Some(self.depth);
+        }
+        let result = search::iterative_deepening(
+            &self.board,
+            &limits,
+            &mut self.tt,
+            eval::eval,
+            &self.params,
+            stop,
+            report,
+        );
+        return Ok(result);
+    }
+}
+
+/// An engine used from several threads, e.g. by a search running in the background while
+/// the caller wants to stop it. A search takes its stop flag when it is requested, before
+/// it waits for the engine, so `stop` reaches both the running search and the searches
+/// queued behind it, but never a search requested after it.
+pub struct SharedEngine {
+    engine: Mutex<Engine>,
+    /// The stop flag of all searches requested since the last `stop`.
+    stop: Mutex<Arc<AtomicBool>>,
+}
+
+impl SharedEngine {
+    pub fn new(engine: Engine) -> SharedEngine {
+        return SharedEngine {
+            engine: Mutex::new(engine),
+            stop: Mutex::new(Arc::new(AtomicBool::new(false))),
+        };
+    }
+
+    /// Waits for a running search to finish. A panic during a search poisons the lock,
+    /// the engine itself is still usable.
+    pub fn lock(&self) -> MutexGuard<'_, Engine> {
+        return self.engine.lock().unwrap_or_else(|err| err.into_inner());
+    }
+
+    /// The stop flag for a search requested now. Take it on the requesting thread, before
+    /// handing the search to another thread or waiting for the engine.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        return self
+            .stop
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+    }
+
+    /// Searches the current position once no other search is running. The UCI binary
+    /// searches on its own thread with `stop_flag` instead.
+    #[allow(dead_code)]
+    pub fn search(&self, limits: &SearchLimits) -> Result<SearchResult, PositionError> {
+        let stop = self.stop_flag();
+        return self.lock().search(limits, stop, |_| {});
+    }
+
+    /// Aborts the running search, which then returns the best move found so far, and
+    /// every search still waiting for it.
+    pub fn stop(&self) {
+        let mut stop = self.stop.lock().unwrap_or_else(|err| err.into_inner());
+        stop.store(true, Ordering::Relaxed);
+        *stop = Arc::new(AtomicBool::new(false));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_position() {
+        let mut engine = Engine::new(1);
+        assert!(engine.set_position(None, &["e2e4", "e7e5"]).is_ok());
+        assert_eq!(
+            engine.board().fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2"
+        );
+
+        let fen = "k7/5R2/6R1/8/8/8/4K3/8 w - - 0 1";
+        assert!(engine.set_position(Some(fen), &[]).is_ok());
+        assert_eq!(engine.board().fen(), fen);
+
+        assert_eq!(
+            engine.set_position(None, &["e2e5"]),
+            Err(PositionError::IllegalMove("e2e5".to_string()))
+        );
+        assert!(engine.set_position(Some("not a fen"), &[]).is_err());
+        assert_eq!(engine.board().fen(), fen);
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let mut engine = Engine::new(1);
+        assert_eq!(engine.pop(), None);
+        assert!(engine.push("e2e4").is_ok());
+        assert!(engine.push("e2e4").is_err());
+        assert!(engine.push("c7c5").is_ok());
+        assert_eq!(engine.pop(), Some("c7c5".to_string()));
+        assert_eq!(engine.pop(), Some("e2e4".to_string()));
+        assert_eq!(engine.board().fen(), Board::start_pos().fen());
+    }
+
+    #[test]
+    fn search_keeps_table() {
+        let mut engine = Engine::new(1);
+        assert!(engine.set_position(None, &["e2e4"]).is_ok());
+        let stop = Arc::new(AtomicBool::new(false));
+        let first = engine
+            .search(&SearchLimits::depth(4), stop.clone(), |_| {})
+            .unwrap();
+        let second = engine
+            .search(&SearchLimits::depth(4), stop.clone(), |_| {})
+            .unwrap();
+        assert_eq!(first.best_move, second.best_move);
+        assert!(second.nodes < first.nodes);
+
+        engine.new_game();
+        assert_eq!(engine.board().fen(), Board::start_pos().fen());
+    }
+
+    #[test]
+    fn default_depth_without_own_clock() {
+        let mut engine = Engine::new(1);
+        engine.depth = 2;
+        assert!(engine.set_position(None, &["e2e4"]).is_ok());
+        let limits = SearchLimits {
+            wtime: Some(1000),
+            ..SearchLimits::default()
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let result = engine.search(&limits, stop, |_| {}).unwrap();
+        assert_eq!(result.depth, 2);
+    }
+
+    #[test]
+    fn stop_then_search() {
+        let shared = Arc::new(SharedEngine::new(Engine::new(1)));
+        // a stop without a running search does not abort the next one
+        shared.stop();
+        let result = shared.search(&SearchLimits::depth(3)).unwrap();
+        assert_eq!(result.depth, 3);
+
+        // an infinite search only returns once it was stopped, so both searches return
+        // whether the stop comes before, while or after they hold the engine
+        let infinite = || {
+            let shared = shared.clone();
+            let stop = shared.stop_flag();
+            return std::thread::spawn(move || {
+                let limits = SearchLimits {
+                    infinite: true,
+                    ..SearchLimits::default()
+                };
+                return shared.lock().search(&limits, stop, |_| {}).unwrap();
+            });
+        };
+        let running = infinite();
+        let queued = infinite();
+        shared.stop();
+        running.join().unwrap();
+        queued.join().unwrap();
+
+        let result = shared.search(&SearchLimits::depth(2)).unwrap();
+        assert_eq!(result.depth, 2);
+    }
+
+    #[test]
+    fn search_finished_game() {
+        let mut engine = Engine::new(1);
+        let fen = "k7/5R2/6R1/8/8/8/4K3/8 w - - 0 1";
+        assert!(engine.set_position(Some(fen), &["g6g8"]).is_ok());
+        let stop = Arc::new(AtomicBool::new(false));
+        let result = engine.search(&SearchLimits::depth(2), stop, |_| {});
+        assert_eq!(result.err(), Some(PositionError::NoPosition));
+    }
+}
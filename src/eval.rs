@@ -1,24 +1,146 @@
 use pleco::helper::Helper;
-use pleco::{Board, PieceType, Player};
+use pleco::{BitBoard, Board, PieceType, Player, SQ};
+use std::ops::{Add, Mul, Sub};
 
-pub fn piece_values(piece: PieceType) -> f32 {
+use crate::pawns;
+use crate::score::{self, Score};
+
+/// Game phase of a position with all pieces on the board.
+pub const MAX_PHASE: i32 = 24;
+
+/// A score with a middlegame and an endgame value. Terms are added up separately for both
+/// phases and only blended into one score at the end, by how much material is left.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TaperedScore {
+    pub mg: Score,
+    pub eg: Score,
+}
+
+impl TaperedScore {
+    pub const fn new(mg: Score, eg: Score) -> TaperedScore {
+        return TaperedScore { mg, eg };
+    }
+
+    /// Interpolates between the endgame value at phase 0 and the middlegame value at
+    /// `MAX_PHASE`.
+    pub fn taper(self, phase: i32) -> Score {
+        let phase = phase.clamp(0, MAX_PHASE);
+        return (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE;
+    }
+}
+
+impl Add for TaperedScore {
+    type Output = TaperedScore;
+    fn add(self, other: TaperedScore) -> TaperedScore {
+        return TaperedScore::new(self.mg + other.mg, self.eg + other.eg);
+    }
+}
+
+impl Sub for TaperedScore {
+    type Output = TaperedScore;
+    fn sub(self, other: TaperedScore) -> TaperedScore {
+        return TaperedScore::new(self.mg - other.mg, self.eg - other.eg);
+    }
+}
+
+impl Mul<Score> for TaperedScore {
+    type Output = TaperedScore;
+    fn mul(self, factor: Score) -> TaperedScore {
+        return TaperedScore::new(self.mg * factor, self.eg * factor);
+    }
+}
+
+// middlegame and endgame weights of the evaluation terms, per unit of the term
+const PINNED: TaperedScore = TaperedScore::new(40, 25);
+const ATTACKING: TaperedScore = TaperedScore::new(50, 35);
+const DEFENDING: TaperedScore = TaperedScore::new(50, 35);
+const BISHOP_PAIR: TaperedScore = TaperedScore::new(30, 50);
+const ROOK_OPEN_FILE: TaperedScore = TaperedScore::new(25, 10);
+const ROOK_SEMI_OPEN_FILE: TaperedScore = TaperedScore::new(12, 6);
+const ROOK_SEVENTH: TaperedScore = TaperedScore::new(15, 30);
+const KNIGHT_OUTPOST: TaperedScore = TaperedScore::new(20, 10);
+/// Per own pawn on the squares of the bishop's color.
+const BAD_BISHOP: TaperedScore = TaperedScore::new(-3, -6);
+const TRAPPED: TaperedScore = TaperedScore::new(-60, -40);
+
+// mobility bonuses by the number of squares a piece can move to, middlegame and endgame
+const KNIGHT_MOBILITY_MG: [Score; 9] = [-30, -20, -8, -3, 2, 6, 10, 13, 16];
+const KNIGHT_MOBILITY_EG: [Score; 9] = [-40, -28, -15, -8, 2, 6, 9, 11, 13];
+const BISHOP_MOBILITY_MG: [Score; 14] = [-25, -12, 2, 8, 14, 19, 23, 26, 29, 31, 34, 37, 39, 42];
+const BISHOP_MOBILITY_EG: [Score; 14] = [-30, -15, -3, 6, 12, 18, 23, 27, 30, 33, 35, 37, 39, 41];
+const ROOK_MOBILITY_MG: [Score; 15] = [-30, -14, -6, -3, -1, 3, 6, 9, 12, 14, 16, 18, 20, 22, 24];
+const ROOK_MOBILITY_EG: [Score; 15] = [-40, -20, -4, 6, 14, 22, 28, 33, 38, 42, 46, 49, 51, 53, 55];
+const QUEEN_MOBILITY_MG: [Score; 28] = [
+    -20, -12, -6, -3, 0, 3, 5, 7, 9, 11, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
+    27, 28, 29, 30,
+];
+const QUEEN_MOBILITY_EG: [Score; 28] = [
+    -30, -20, -12, -6, 0, 5, 10, 14, 18, 22, 25, 28, 31, 34, 36, 38, 40, 42, 44, 45, 46, 47, 48,
+    49, 50, 51, 52, 53,
+];
+
+// king safety, which only matters in the middlegame
+/// Bonus of the nearest own pawn in front of the king on its file and the files next to
+/// it, by how many ranks it is ahead of the king.
+const SHIELD: [Score; 3] = [0, 20, 10];
+const SHIELD_MISSING: Score = -20;
+/// Penalty of the nearest enemy pawn storming one of these files, by how many ranks it
+/// is ahead of the king. Halved when an own pawn blocks it.
+const STORM: [Score; 5] = [0, -5, -25, -15, -5];
+/// Penalty of a file next to the king without own pawns, and the extra penalty when
+/// there are no enemy pawns on it either.
+const SEMI_OPEN_FILE: Score = -15;
+const OPEN_FILE: Score = -10;
+/// Maximum king danger, which grows with the square of the attack units.
+const MAX_KING_DANGER: Score = 800;
+const KING_DANGER_DIVISOR: Score = 4;
+
+/// Middlegame value of a piece, also used by the search to judge captures.
+pub fn piece_values(piece: PieceType) -> Score {
+    return match piece {
+        PieceType::P => 100,
+        PieceType::N => 280,
+        PieceType::B => 320,
+        PieceType::R => 479,
+        PieceType::Q => 929,
+        PieceType::K => 60_000,
+        PieceType::None => 0,
+        PieceType::All => 0,
+    };
+}
+
+/// Endgame value of a piece. Pawns are worth more once they can run, minor pieces less.
+fn endgame_piece_values(piece: PieceType) -> Score {
     return match piece {
-        PieceType::P => 100.0,
-        PieceType::N => 280.0,
-        PieceType::B => 320.0,
-        PieceType::R => 479.0,
-        PieceType::Q => 929.0,
-        PieceType::K => 60_000.0,
-        PieceType::None => 0.0,
-        PieceType::All => 0.0,
+        PieceType::P => 125,
+        PieceType::N => 265,
+        PieceType::B => 300,
+        PieceType::R => 500,
+        PieceType::Q => 940,
+        _ => piece_values(piece),
     };
 }
 
-fn count_piece_material(board: &Board, player: Player, piece: PieceType) -> f32 {
-    return piece_values(piece) * board.count_piece(player, piece) as f32;
+/// How far the game is from the endgame, from the minor pieces, rooks and queens left:
+/// `MAX_PHASE` with all of them on the board, 0 with only kings and pawns.
+pub fn game_phase(board: &Board) -> i32 {
+    let mut phase = 0;
+    for player in [Player::White, Player::Black] {
+        phase += board.count_piece(player, PieceType::N) as i32;
+        phase += board.count_piece(player, PieceType::B) as i32;
+        phase += 2 * board.count_piece(player, PieceType::R) as i32;
+        phase += 4 * board.count_piece(player, PieceType::Q) as i32;
+    }
+    // promotions can add more than the starting material
+    return phase.min(MAX_PHASE);
 }
 
-fn count_material(board: &Board) -> f32 {
+fn count_piece_material(board: &Board, player: Player, piece: PieceType) -> TaperedScore {
+    let count = board.count_piece(player, piece) as Score;
+    return TaperedScore::new(piece_values(piece), endgame_piece_values(piece)) * count;
+}
+
+fn count_material(board: &Board) -> TaperedScore {
     let wp = count_piece_material(board, Player::White, PieceType::P);
     let wn = count_piece_material(board, Player::White, PieceType::N);
     let wb = count_piece_material(board, Player::White, PieceType::B);
@@ -39,28 +161,272 @@ fn count_material(board: &Board) -> f32 {
     return material;
 }
 
-fn king_safety(board: &Board) -> f32 {
+/// Attack units a piece adds when it attacks the king zone.
+fn attack_weight(piece: PieceType) -> Score {
+    return match piece {
+        PieceType::N | PieceType::B => 2,
+        PieceType::R => 3,
+        PieceType::Q => 5,
+        _ => 0,
+    };
+}
+
+/// Attack units a piece adds when it can give a check from a square that is not
+/// defended.
+fn safe_check_weight(piece: PieceType) -> Score {
+    return match piece {
+        PieceType::N => 4,
+        PieceType::B => 3,
+        PieceType::R => 5,
+        PieceType::Q => 6,
+        _ => 0,
+    };
+}
+
+/// All squares attacked by the pieces of `player`.
+fn attacked_by(board: &Board, player: Player) -> BitBoard {
+    let mut attacks = BitBoard(0);
+    for sq in board.get_occupied_player(player) {
+        let piece = board.piece_at_sq(sq).type_of();
+        attacks |= board.attacks_from(piece, sq, player);
+    }
+    return attacks;
+}
+
+/// The pawn shield and pawn storm on the king's file and the files next to it, and how
+/// open these files are. The files of a king on the edge are those of the b or g file.
+fn king_shelter(board: &Board, player: Player) -> Score {
+    let king = board.king_sq(player);
+    let king_rank = player.relative_rank_of_sq(king) as Score;
+    let center = (king.0 % 8).clamp(1, 6);
+    let own = board.piece_bb(player, PieceType::P);
+    let enemy = board.piece_bb(!player, PieceType::P);
+
+    // ranks ahead of the king of the nearest pawn in front of it
+    let nearest = |pawns: BitBoard| {
+        return pawns
+            .map(|sq| player.relative_rank_of_sq(sq) as Score - king_rank)
+            .filter(|ranks| *ranks > 0)
+            .min();
+    };
+
+    let mut score = 0;
+    for file in center - 1..=center + 1 {
+        let file_bb = SQ(file).file_bb();
+        let shield = nearest(own & file_bb);
+        let storm = nearest(enemy & file_bb);
+        score += match shield {
+            Some(ranks) if (ranks as usize) < SHIELD.len() => SHIELD[ranks as usize],
+            _ => SHIELD_MISSING,
+        };
+        if let Some(ranks) = storm {
+            if (ranks as usize) < STORM.len() {
+                let blocked = shield == Some(ranks - 1);
+                score += match blocked {
+                    true => STORM[ranks as usize] / 2,
+                    false => STORM[ranks as usize],
+                };
+            }
+        }
+        if (own & file_bb).is_empty() {
+            score += SEMI_OPEN_FILE;
+            if (enemy & file_bb).is_empty() {
+                score += OPEN_FILE;
+            }
+        }
+    }
+    return score;
+}
+
+/// How much danger the king of `player` is in from the enemy pieces, by the attack units
+/// of the pieces that attack the squares around it and of the safe checks they have.
+fn king_danger(board: &Board, player: Player) -> Score {
     let hlp = Helper::new();
-    let wo = board.occupied_white(); //bitboard with occupency for white
-    let bo = board.occupied_black();
+    let them = !player;
+    let king = board.king_sq(player);
+    let zone = hlp.ring_distance(king, 0) | king.to_bb();
+    // squares the enemy may check from without being taken
+    let safe = !(attacked_by(board, player) | board.get_occupied_player(them));
+
+    let mut attackers = 0;
+    let mut units = 0;
+    for piece in [PieceType::N, PieceType::B, PieceType::R, PieceType::Q] {
+        // a piece of this type standing on these squares would give check
+        let checks = board.attacks_from(piece, king, player) & safe;
+        let mut can_check = false;
+        for sq in board.piece_bb(them, piece) {
+            let attacks = board.attacks_from(piece, sq, them);
+            let zone_attacks = (attacks & zone).count_bits() as Score;
+            if zone_attacks > 0 {
+                attackers += 1;
+                units += attack_weight(piece) + zone_attacks;
+            }
+            can_check |= (attacks & checks).is_not_empty();
+        }
+        if can_check {
+            units += safe_check_weight(piece);
+        }
+    }
+    // a single piece rarely mates on its own
+    if attackers < 2 {
+        return 0;
+    }
+    if board.count_piece(them, PieceType::Q) == 0 {
+        units /= 2;
+    }
+    return (units * units / KING_DANGER_DIVISOR).min(MAX_KING_DANGER);
+}
+
+/// King safety of white minus that of black. It only counts in the middlegame, in the
+/// endgame the king is a piece like any other.
+fn king_safety(board: &Board) -> TaperedScore {
+    let white = king_shelter(board, Player::White) - king_danger(board, Player::White);
+    let black = king_shelter(board, Player::Black) - king_danger(board, Player::Black);
+    return TaperedScore::new(white - black, 0);
+}
+
+fn mobility_bonus(piece: PieceType, squares: usize) -> TaperedScore {
+    let (mg, eg): (&[Score], &[Score]) = match piece {
+        PieceType::N => (&KNIGHT_MOBILITY_MG, &KNIGHT_MOBILITY_EG),
+        PieceType::B => (&BISHOP_MOBILITY_MG, &BISHOP_MOBILITY_EG),
+        PieceType::R => (&ROOK_MOBILITY_MG, &ROOK_MOBILITY_EG),
+        PieceType::Q => (&QUEEN_MOBILITY_MG, &QUEEN_MOBILITY_EG),
+        _ => return TaperedScore::default(),
+    };
+    let idx = squares.min(mg.len() - 1);
+    return TaperedScore::new(mg[idx], eg[idx]);
+}
+
+/// Mobility of `player`'s knights, bishops, rooks and queens: the squares each of them
+/// attacks that are neither occupied by its own pieces nor attacked by an enemy pawn.
+fn side_mobility(board: &Board, player: Player) -> TaperedScore {
+    let enemy_pawns = board.piece_bb(!player, PieceType::P).0;
+    let pawn_attacks = BitBoard(pawns::pawn_attacks(enemy_pawns, !player));
+    let area = !(board.get_occupied_player(player) | pawn_attacks);
+    let mut score = TaperedScore::default();
+    for piece in [PieceType::N, PieceType::B, PieceType::R, PieceType::Q] {
+        for sq in board.piece_bb(player, piece) {
+            let squares = (board.attacks_from(piece, sq, player) & area).count_bits();
+            score = score + mobility_bonus(piece, squares as usize);
+        }
+    }
+    return score;
+}
+
+fn mobility(board: &Board) -> TaperedScore {
+    return side_mobility(board, Player::White) - side_mobility(board, Player::Black);
+}
+
+/// The difference of a term counted for white and for black.
+fn both_sides(board: &Board, term: fn(&Board, Player) -> Score) -> Score {
+    return term(board, Player::White) - term(board, Player::Black);
+}
+
+fn bishop_pair(board: &Board, player: Player) -> Score {
+    return (board.count_piece(player, PieceType::B) >= 2) as Score;
+}
+
+/// Rooks on files without any pawns.
+fn rooks_on_open_files(board: &Board, player: Player) -> Score {
+    let pawns = board.piece_bb_both_players(PieceType::P);
+    return board
+        .piece_bb(player, PieceType::R)
+        .filter(|sq| (pawns & sq.file_bb()).is_empty())
+        .count() as Score;
+}
+
+/// Rooks on files with enemy pawns but none of their own.
+fn rooks_on_semi_open_files(board: &Board, player: Player) -> Score {
+    let own = board.piece_bb(player, PieceType::P);
+    let enemy = board.piece_bb(!player, PieceType::P);
+    return board
+        .piece_bb(player, PieceType::R)
+        .filter(|sq| (own & sq.file_bb()).is_empty() && (enemy & sq.file_bb()).is_not_empty())
+        .count() as Score;
+}
 
-    let wksq = board.king_sq(Player::White);
-    let white_around = hlp.ring_distance(wksq, 0);
-    let white_safety = (white_around & wo).count_bits() as f32;
+/// Rooks on the 7th rank, where they attack pawns that have not moved yet or cut off the
+/// king on the last rank.
+fn rooks_on_seventh(board: &Board, player: Player) -> Score {
+    let seventh = player.relative_square(SQ::A7).rank_bb();
+    let last = player.relative_square(SQ::A8).rank_bb();
+    let enemy_pawns = board.piece_bb(!player, PieceType::P);
+    if (enemy_pawns & seventh).is_empty() && (board.king_sq(!player).to_bb() & last).is_empty() {
+        return 0;
+    }
+    return (board.piece_bb(player, PieceType::R) & seventh).count_bits() as Score;
+}
 
-    let bksq = board.king_sq(Player::Black);
-    let black_around = hlp.ring_distance(bksq, 0);
-    let black_safety = (black_around & bo).count_bits() as f32;
-    return white_safety - black_safety;
+/// Knights on the 4th to 6th rank, defended by a pawn, that no enemy pawn can ever attack.
+fn knight_outposts(board: &Board, player: Player) -> Score {
+    let own = board.piece_bb(player, PieceType::P).0;
+    let enemy = board.piece_bb(!player, PieceType::P).0;
+    return board
+        .piece_bb(player, PieceType::N)
+        .filter(|sq| {
+            let rank = player.relative_rank_of_sq(*sq) as u8;
+            let supported = pawns::pawn_attacks(sq.to_bb().0, !player) & own != 0;
+            let attackable = pawns::attack_span(sq.to_bb().0, player) & enemy != 0;
+            return (3..=5).contains(&rank) && supported && !attackable;
+        })
+        .count() as Score;
 }
 
-fn pinned_pieces(board: &Board) -> f32 {
-    let wp = board.pieces_pinned(Player::White).count_bits() as f32;
-    let bp = board.pieces_pinned(Player::Black).count_bits() as f32;
+/// Own pawns on the squares of the bishops' color, which block their diagonals.
+fn bad_bishops(board: &Board, player: Player) -> Score {
+    let pawns = board.piece_bb(player, PieceType::P);
+    let mut blocking = 0;
+    for sq in board.piece_bb(player, PieceType::B) {
+        let color = match (sq.to_bb() & BitBoard::DARK_SQUARES).is_empty() {
+            true => BitBoard::LIGHT_SQUARES,
+            false => BitBoard::DARK_SQUARES,
+        };
+        blocking += (pawns & color).count_bits() as Score;
+    }
+    return blocking;
+}
+
+/// Pieces caught in well known traps: a bishop on a7 or h7 cut off by a pawn on b6 or
+/// g6, and a rook in the corner behind a king that has moved towards it.
+fn trapped_pieces(board: &Board, player: Player) -> Score {
+    let relative = |sq: SQ| player.relative_square(sq);
+    let has = |player: Player, piece: PieceType, sq: SQ| {
+        return (board.piece_bb(player, piece) & relative(sq).to_bb()).is_not_empty();
+    };
+    let king = board.king_sq(player);
+    let mut trapped = 0;
+    if has(player, PieceType::B, SQ::A7) && has(!player, PieceType::P, SQ::B6) {
+        trapped += 1;
+    }
+    if has(player, PieceType::B, SQ::H7) && has(!player, PieceType::P, SQ::G6) {
+        trapped += 1;
+    }
+    let kingside = [SQ::F1, SQ::G1].map(relative).contains(&king);
+    if kingside
+        && [SQ::G1, SQ::H1, SQ::H2]
+            .iter()
+            .any(|sq| has(player, PieceType::R, *sq))
+    {
+        trapped += 1;
+    }
+    let queenside = [SQ::B1, SQ::C1, SQ::D1].map(relative).contains(&king);
+    if queenside
+        && [SQ::A1, SQ::B1, SQ::A2]
+            .iter()
+            .any(|sq| has(player, PieceType::R, *sq))
+    {
+        trapped += 1;
+    }
+    return trapped;
+}
+
+fn pinned_pieces(board: &Board) -> Score {
+    let wp = board.pieces_pinned(Player::White).count_bits() as Score;
+    let bp = board.pieces_pinned(Player::Black).count_bits() as Score;
     return bp - wp; //enemy pinned pieces are good
 }
 
-fn attacking_defending(board: &Board) -> (f32, f32) {
+fn attacking_defending(board: &Board) -> (Score, Score) {
     let ao = board.occupied();
     let wo = board.occupied_white();
     let bo = board.occupied_black();
@@ -88,53 +454,38 @@ fn attacking_defending(board: &Board) -> (f32, f32) {
 
     let attacking = white_attackers - black_attackers;
     let defending = white_defenders - black_defenders;
-    return (attacking as f32, defending as f32);
+    return (attacking as Score, defending as Score);
 }
 
-fn num_big_pieces(board: &Board) -> u8 {
-    let pieces = board.occupied();
-    let white_pawn = board.piece_bb(Player::White, PieceType::P);
-    let black_pawn = board.piece_bb(Player::Black, PieceType::P);
-    //let white_king = board.king_sq(Player::White).to_bb();
-    //let black_king = board.king_sq(Player::Black).to_bb();
-    let big_pieces = pieces & !white_pawn & !black_pawn; //&!white_king&!black_king;
-    let num = big_pieces.count_bits();
-    return num;
-}
-
-pub fn eval(board: &Board) -> f32 {
-    if board.checkmate() {
-        let turn: f32 = match &board.turn() {
-            Player::White => 1.0,
-            Player::Black => -1.0,
-        };
-        let score = -turn * (9999.0 - board.ply() as f32);
-        return score;
-    }
-
-    if board.stalemate() {
-        return 0.0;
-    }
-
+/// Static evaluation from white's point of view. Checkmate and stalemate are left to the
+/// search, which finds positions without legal moves before it evaluates them. The result
+/// always stays below the mate scores.
+pub fn eval(board: &Board) -> Score {
     let material = count_material(board);
     let (middle, end) = board.psq().centipawns();
+    let psq = TaperedScore::new((0.01 * middle) as Score, (0.01 * end) as Score);
     let king_safety = king_safety(board);
     let pinned = pinned_pieces(board);
     let (attacking, defending) = attacking_defending(board);
 
-    let mut psq = middle;
-    if num_big_pieces(board) < 8 {
-        psq = end;
-    }
-
     let score = material
-        + 0.01 * psq as f32
-        + 20.0 * king_safety
-        + 40.0 * pinned
-        + 50.0 * attacking
-        + 50.0 * defending;
-
-    return score;
+        + psq
+        + king_safety
+        + PINNED * pinned
+        + ATTACKING * attacking
+        + DEFENDING * defending
+        + mobility(board)
+        + BISHOP_PAIR * both_sides(board, bishop_pair)
+        + ROOK_OPEN_FILE * both_sides(board, rooks_on_open_files)
+        + ROOK_SEMI_OPEN_FILE * both_sides(board, rooks_on_semi_open_files)
+        + ROOK_SEVENTH * both_sides(board, rooks_on_seventh)
+        + KNIGHT_OUTPOST * both_sides(board, knight_outposts)
+        + BAD_BISHOP * both_sides(board, bad_bishops)
+        + TRAPPED * both_sides(board, trapped_pieces)
+        + pawns::evaluate(board);
+
+    let bound = score::MATE_BOUND - 1;
+    return score.taper(game_phase(board)).clamp(-bound, bound);
 }
 
 #[cfg(test)]
@@ -143,7 +494,7 @@ mod tests {
 
     #[test]
     fn start_pos() {
-        assert_eq!(count_material(&Board::start_pos()), 0.0);
+        assert_eq!(count_material(&Board::start_pos()).mg, 0);
     }
 
     #[test]
@@ -151,7 +502,7 @@ mod tests {
         let board =
             Board::from_fen("rnbqkbnr/ppp2ppp/4p3/3p4/3P4/4P3/PPP2PPP/RNBQKBNR w KQkq - 0 3")
                 .unwrap();
-        assert_eq!(count_material(&board), 0.0);
+        assert_eq!(count_material(&board).mg, 0);
     }
 
     #[test]
@@ -159,7 +510,7 @@ mod tests {
         let board =
             Board::from_fen("rnb1kbnr/ppp2ppp/4p3/3p2Q1/3P4/4P3/PPP2PPP/RNB1KBNR b KQkq - 0 4")
                 .unwrap();
-        assert_eq!(count_material(&board), 929.0);
+        assert_eq!(count_material(&board).mg, 929);
     }
 
     #[test]
@@ -167,7 +518,7 @@ mod tests {
         let board =
             Board::from_fen("rnb1kbnr/ppp2ppp/4p3/3p4/3P3Q/4Pq2/PPP2PPP/RNB1KB1R w KQkq - 0 6")
                 .unwrap();
-        assert_eq!(count_material(&board), -280.0);
+        assert_eq!(count_material(&board).mg, -280);
     }
 
     #[test]
@@ -175,66 +526,230 @@ mod tests {
         let board1 =
             Board::from_fen("rnb1kbnr/ppp2ppQ/4p3/3p4/3P4/4Pq2/PPP2PPP/RNB1KB1R b KQkq - 0 6")
                 .unwrap();
-        assert_eq!(count_material(&board1), -180.0);
+        assert_eq!(count_material(&board1).mg, -180);
 
         let board2 =
             Board::from_fen("rnb1kbn1/ppp2ppr/4p3/3p4/3P4/4Pq2/PPP2PPP/RNB1KB1R w KQq - 0 7")
                 .unwrap();
-        assert_eq!(count_material(&board2), -1109.0);
+        assert_eq!(count_material(&board2).mg, -1109);
+    }
+
+    #[test]
+    fn endgame_material() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        assert_eq!(count_material(&board), TaperedScore::new(100, 125));
+    }
+
+    #[test]
+    fn phase() {
+        assert_eq!(game_phase(&Board::start_pos()), MAX_PHASE);
+        let pawns = Board::from_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap();
+        assert_eq!(game_phase(&pawns), 0);
+        let rooks = Board::from_fen("r3k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(game_phase(&rooks), 4);
+        let queens = Board::from_fen("QQQQk3/8/8/8/8/8/8/QQQQK3 w - - 0 1").unwrap();
+        assert_eq!(game_phase(&queens), MAX_PHASE);
+    }
+
+    #[test]
+    fn taper() {
+        let score = TaperedScore::new(100, 20);
+        assert_eq!(score.taper(MAX_PHASE), 100);
+        assert_eq!(score.taper(0), 20);
+        assert_eq!(score.taper(MAX_PHASE / 2), 60);
+        assert_eq!(
+            (score + score - TaperedScore::new(0, 40)) * 2,
+            TaperedScore::new(400, 0)
+        );
+    }
+
+    #[test]
+    fn no_jump_between_phases() {
+        // trading pieces one at a time only moves the evaluation a little beyond the
+        // material that left the board
+        let fens = [
+            "r1b1k2r/1ppp1ppp/2n2n2/8/8/2N2N2/PPPP1PPP/R1B1K2R w KQkq - 0 1",
+            "r1b1k2r/1ppp1ppp/5n2/8/8/5N2/PPPP1PPP/R1B1K2R w KQkq - 0 1",
+            "r3k2r/1ppp1ppp/5n2/8/8/5N2/PPPP1PPP/R3K2R w KQkq - 0 1",
+            "r3k2r/1ppp1ppp/8/8/8/8/PPPP1PPP/R3K2R w KQkq - 0 1",
+            "4k3/1ppp1ppp/8/8/8/8/PPPP1PPP/4K3 w - - 0 1",
+        ];
+        let scores: Vec<Score> = fens
+            .iter()
+            .map(|fen| eval(&Board::from_fen(fen).unwrap()))
+            .collect();
+        for pair in scores.windows(2) {
+            assert!((pair[0] - pair[1]).abs() < 60, "{:?}", scores);
+        }
     }
 
     #[test]
     fn safety() {
-        let b1 = Board::from_fen("k7/8/8/8/8/8/1PPP4/2K5 w - - 0 1").unwrap();
-        let k1 = king_safety(&b1);
-        assert_eq!(k1, 3.0);
+        assert_eq!(king_safety(&Board::start_pos()), TaperedScore::default());
+
+        let intact = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(king_shelter(&intact, Player::White), SHIELD[1] * 3);
+        assert_eq!(king_safety(&intact), TaperedScore::default());
+
+        let pushed = Board::from_fen("6k1/5ppp/8/8/6P1/8/5P1P/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            king_shelter(&pushed, Player::White),
+            SHIELD[1] * 2 + SHIELD_MISSING
+        );
+
+        let open = Board::from_fen("6k1/5p1p/8/8/8/8/5P1P/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            king_shelter(&open, Player::White),
+            SHIELD[1] * 2 + SHIELD_MISSING + SEMI_OPEN_FILE + OPEN_FILE
+        );
+
+        // the pawn on g3 is blocked by the one on g2
+        let storm = Board::from_fen("6k1/5p1p/8/8/8/6p1/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            king_shelter(&storm, Player::White),
+            SHIELD[1] * 3 + STORM[2] / 2
+        );
+    }
 
-        let b2 = Board::from_fen("k7/pp6/8/8/8/8/1PPP4/2K5 w - - 0 1").unwrap();
-        let k2 = king_safety(&b2);
-        assert_eq!(k2, 1.0);
+    #[test]
+    fn danger() {
+        // the queen and the knight both attack f2 and h2
+        let attacked = Board::from_fen("6k1/5ppp/8/8/6nq/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert!(king_danger(&attacked, Player::White) > 0);
+        assert_eq!(king_danger(&attacked, Player::Black), 0);
+        let safety = king_safety(&attacked);
+        assert!(safety.mg < 0);
+        assert_eq!(safety.eg, 0);
+
+        let lone_queen = Board::from_fen("6k1/5ppp/8/8/7q/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(king_danger(&lone_queen, Player::White), 0);
+
+        // the rook on b1 takes away the check on the back rank
+        let defended = Board::from_fen("r5k1/5ppp/8/8/6nq/8/5PPP/1R4K1 w - - 0 1").unwrap();
+        let undefended = Board::from_fen("r5k1/5ppp/8/8/6nq/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert!(king_danger(&undefended, Player::White) > king_danger(&defended, Player::White));
+    }
 
-        let b3 = Board::from_fen("3k4/ppppp3/8/8/8/8/2P5/2K5 w - - 0 1").unwrap();
-        let k3 = king_safety(&b3);
-        assert_eq!(k3, -2.0);
+    #[test]
+    fn test_mobility() {
+        assert_eq!(mobility(&Board::start_pos()), TaperedScore::default());
+
+        // a knight in the center against one in the corner
+        let board = Board::from_fen("n3k3/8/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        let white = side_mobility(&board, Player::White);
+        assert_eq!(white, mobility_bonus(PieceType::N, 8));
+        let black = side_mobility(&board, Player::Black);
+        assert_eq!(black, mobility_bonus(PieceType::N, 2));
+        assert!(mobility(&board).mg > 0);
+
+        // squares covered by enemy pawns and own pieces do not count
+        let board = Board::from_fen("4k3/8/2p1p3/8/3N4/1P6/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            side_mobility(&board, Player::White),
+            mobility_bonus(PieceType::N, 5)
+        );
+
+        // a rook boxed in by its own pieces
+        let board = Board::from_fen("4k3/8/8/8/8/8/PP6/RN2K3 w - - 0 1").unwrap();
+        assert_eq!(
+            side_mobility(&board, Player::White),
+            mobility_bonus(PieceType::R, 0) + mobility_bonus(PieceType::N, 3)
+        );
+        assert_eq!(
+            mobility_bonus(PieceType::Q, 40),
+            mobility_bonus(PieceType::Q, 27)
+        );
+    }
 
-        let b4 = Board::from_fen("2rk4/ppppp2n/8/P1r2Q2/1P5r/8/2PP4/2K5 w - - 0 1").unwrap();
-        let k4 = king_safety(&b4);
-        assert_eq!(k4, -2.0);
+    fn white_and_black(fen: &str, term: fn(&Board, Player) -> Score) -> (Score, Score) {
+        let board = Board::from_fen(fen).unwrap();
+        return (term(&board, Player::White), term(&board, Player::Black));
+    }
+
+    #[test]
+    fn piece_terms() {
+        let start = Board::start_pos();
+        for term in [
+            bishop_pair,
+            rooks_on_open_files,
+            rooks_on_semi_open_files,
+            rooks_on_seventh,
+            knight_outposts,
+            bad_bishops,
+            trapped_pieces,
+        ] {
+            assert_eq!(both_sides(&start, term), 0);
+        }
+
+        let fen = "4k3/8/8/8/8/8/8/2B1KB1b w - - 0 1";
+        assert_eq!(white_and_black(fen, bishop_pair), (1, 0));
+
+        // a rook on the open d-file, one on the half-open e-file and one behind its pawn
+        let fen = "4k3/4p3/8/8/8/8/P7/R2RRK2 w - - 0 1";
+        assert_eq!(white_and_black(fen, rooks_on_open_files), (1, 0));
+        assert_eq!(white_and_black(fen, rooks_on_semi_open_files), (1, 0));
+
+        let fen = "6k1/R4ppp/8/8/8/r7/8/6K1 w - - 0 1";
+        assert_eq!(white_and_black(fen, rooks_on_seventh), (1, 0));
+
+        // the knight on d5 is defended by e4 and no black pawn can drive it away, unlike
+        // the one on f5 that g6 covers
+        let fen = "4k3/8/6p1/3N1N2/4P3/8/8/4K3 w - - 0 1";
+        assert_eq!(white_and_black(fen, knight_outposts), (1, 0));
+        let fen = "4k3/2p5/8/3N4/4P3/8/8/4K3 w - - 0 1";
+        assert_eq!(white_and_black(fen, knight_outposts), (0, 0));
+
+        // the bishop on c1 has three of the four pawns on its dark squares
+        let fen = "4k3/8/8/8/3PP3/2P5/1P6/2B1K3 w - - 0 1";
+        assert_eq!(white_and_black(fen, bad_bishops), (3, 0));
+
+        let fen = "4k3/B7/1p6/8/8/8/8/5KR1 w - - 0 1";
+        assert_eq!(white_and_black(fen, trapped_pieces), (2, 0));
+        // the same traps for black
+        let fen = "5kr1/8/8/8/8/1P6/b7/4K3 w - - 0 1";
+        assert_eq!(white_and_black(fen, trapped_pieces), (0, 2));
     }
 
     #[test]
     fn test_pinned_pieces() {
         let b1 = Board::from_fen("2k5/3p4/8/5B2/8/8/8/2K5 w - - 0 1").unwrap();
         let p1 = pinned_pieces(&b1);
-        assert_eq!(p1, 1.0);
+        assert_eq!(p1, 1);
         let b2 = Board::from_fen("2k5/3p4/2r5/5B2/8/8/2P5/2K5 w - - 0 1").unwrap();
         let p2 = pinned_pieces(&b2);
-        assert_eq!(p2, 0.0);
+        assert_eq!(p2, 0);
 
         let b3 = Board::from_fen("3k4/8/8/3r4/8/8/3P4/3K4 w - - 0 1").unwrap();
         let p3 = pinned_pieces(&b3);
-        assert_eq!(p3, -1.0);
+        assert_eq!(p3, -1);
 
         let b4 = Board::from_fen("3k4/8/8/3r4/8/1b6/2PP4/3KN2q w - - 0 1").unwrap();
         let p4 = pinned_pieces(&b4);
-        assert_eq!(p4, -3.0);
+        assert_eq!(p4, -3);
     }
 
     #[test]
     fn test_attacking_defending() {
         let b1 = Board::from_fen("1k6/8/8/8/8/8/NNN5/1K6 w - - 0 1").unwrap();
         let (a1, d1) = attacking_defending(&b1);
-        assert_eq!(a1, 0.0);
-        assert_eq!(d1, 3.0);
+        assert_eq!(a1, 0);
+        assert_eq!(d1, 3);
 
         let b2 = Board::from_fen("1k6/nnn5/8/8/5B2/8/NNN5/1K6 w - - 0 1").unwrap();
         let (a2, d2) = attacking_defending(&b2);
-        assert_eq!(a2, 1.0);
-        assert_eq!(d2, 0.0);
+        assert_eq!(a2, 1);
+        assert_eq!(d2, 0);
 
         let b3 = Board::from_fen("2k5/1ppp4/4B3/8/8/8/2PPP3/3K4 w - - 0 1").unwrap();
         let (a3, d3) = attacking_defending(&b3);
-        assert_eq!(a3, 0.0);
-        assert_eq!(d3, 0.0);
+        assert_eq!(a3, 0);
+        assert_eq!(d3, 0);
+    }
+
+    #[test]
+    fn never_a_mate_score() {
+        let board = Board::from_fen("QQQQQQQQ/Q7/8/8/8/7k/8/RRBBNNK1 w - - 0 1").unwrap();
+        assert_eq!(eval(&board), score::MATE_BOUND - 1);
+        assert!(!score::is_mate(eval(&board)));
     }
 }
@@ -1,21 +1,271 @@
+#![allow(clippy::needless_return)]
+// py_fn! and py_class! expand to code that trips these lints
+#![allow(
+    clippy::manual_strip,
+    clippy::too_many_arguments,
+    non_local_definitions
+)]
 extern crate cpython;
-use cpython::{py_fn, py_module_initializer, PyResult, Python};
+use cpython::{
+    exc, py_class, py_fn, py_module_initializer, NoArgs, ObjectProtocol, PyDict, PyErr, PyObject,
+    PyResult, PyTuple, Python, PythonObject,
+};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+mod engine;
 mod eval;
+mod movepick;
+mod pawns;
+mod position;
+mod score;
 mod search;
+mod see;
+mod timeman;
+mod tt;
 #[allow(unused)]
 mod utils;
 
+use position::PositionError;
+use timeman::SearchLimits;
+
 py_module_initializer!(rc2d2, |py, m| {
     m.add(py, "__doc__", "Chess engine in rust")?;
+    m.add_class::<Engine>(py)?;
+    m.add_class::<SearchFuture>(py)?;
     m.add(
         py,
         "find_best_move",
-        py_fn!(py, find_best_move(uci_moves: &str, depth: u8)),
+        py_fn!(py, find_best_move(uci_moves: &str, depth: u8, fen: Option<&str> = None)),
     )?;
     Ok(())
 });
 
-fn find_best_move(_py: Python, moves: &str, depth: u8) -> PyResult<String> {
-    let mv = utils::find_best_move(moves, depth);
-    return Ok(mv);
+// Every method runs without the GIL, so other Python threads (e.g. other games) keep
+// running while it waits for a search on the same engine to finish. `stop` never waits.
+py_class!(class Engine |py| {
+    data engine: Arc<engine::SharedEngine>;
+
+    def __new__(
+        _cls,
+        hash: usize = tt::DEFAULT_SIZE_MB,
+        depth: u8 = engine::DEFAULT_DEPTH,
+        threads: usize = 1
+    ) -> PyResult<Engine> {
+        let mut engine = engine::Engine::new(hash);
+        engine.depth = depth;
+        engine.params.threads = threads.clamp(1, search::MAX_THREADS);
+        return Engine::create_instance(py, Arc::new(engine::SharedEngine::new(engine)));
+    }
+
+    def set_position(&self, fen: Option<&str> = None, moves: &str = "") -> PyResult<PyObject> {
+        let moves = position::parse_moves(moves);
+        let engine = self.engine(py);
+        py.allow_threads(|| engine.lock().set_position(fen, &moves))
+            .map_err(|err| position_error(py, err))?;
+        return Ok(py.None());
+    }
+
+    def push(&self, mv: &str) -> PyResult<PyObject> {
+        let engine = self.engine(py);
+        py.allow_threads(|| engine.lock().push(mv)).map_err(|err| position_error(py, err))?;
+        return Ok(py.None());
+    }
+
+    def pop(&self) -> PyResult<String> {
+        let engine = self.engine(py);
+        return match py.allow_threads(|| engine.lock().pop()) {
+            Some(mv) => Ok(mv),
+            None => Err(value_error(py, "no move to take back")),
+        };
+    }
+
+    def new_game(&self) -> PyResult<PyObject> {
+        let engine = self.engine(py);
+        py.allow_threads(|| engine.lock().new_game());
+        return Ok(py.None());
+    }
+
+    def fen(&self) -> PyResult<String> {
+        let engine = self.engine(py);
+        return Ok(py.allow_threads(|| engine.lock().board().fen()));
+    }
+
+    def set_option(&self, name: &str, value: i64) -> PyResult<PyObject> {
+        let engine = self.engine(py);
+        let known = py.allow_threads(|| engine.lock().set_option(name, value));
+        if !known {
+            return Err(value_error(py, &format!("unknown option: {}", name)));
+        }
+        return Ok(py.None());
+    }
+
+    def search(
+        &self,
+        depth: Option<u8> = None,
+        wtime: Option<u64> = None,
+        btime: Option<u64> = None,
+        winc: Option<u64> = None,
+        binc: Option<u64> = None,
+        movestogo: Option<u64> = None,
+        movetime: Option<u64> = None
+    ) -> PyResult<PyDict> {
+        let limits = SearchLimits {
+            depth,
+            wtime,
+            btime,
+            winc,
+            binc,
+            movestogo,
+            movetime,
+            ..SearchLimits::default()
+        };
+        let engine = self.engine(py);
+        let result = py.allow_threads(|| engine.search(&limits));
+        let result = result.map_err(|err| position_error(py, err))?;
+        return result_dict(py, &result);
+    }
+
+    // Starts the search on a background thread and returns a `SearchFuture` right away.
+    // If `callback` is given, it is called with the result dict from a Python thread once
+    // the search has finished or was stopped. The interpreter waits for that thread
+    // before it exits.
+    def search_async(
+        &self,
+        callback: Option<PyObject> = None,
+        depth: Option<u8> = None,
+        wtime: Option<u64> = None,
+        btime: Option<u64> = None,
+        winc: Option<u64> = None,
+        binc: Option<u64> = None,
+        movestogo: Option<u64> = None,
+        movetime: Option<u64> = None
+    ) -> PyResult<SearchFuture> {
+        let limits = SearchLimits {
+            depth,
+            wtime,
+            btime,
+            winc,
+            binc,
+            movestogo,
+            movetime,
+            ..SearchLimits::default()
+        };
+        let engine = self.engine(py).clone();
+        // report a finished game right away instead of from the future
+        let game_over = py.allow_threads(|| engine.lock().board().generate_moves().is_empty());
+        if game_over {
+            return Err(position_error(py, PositionError::NoPosition));
+        }
+        let slot: SearchSlot = Arc::new((Mutex::new(None), Condvar::new()));
+        let search_slot = slot.clone();
+        // taken here so a `stop` right after this call reaches the search
+        let stop = engine.stop_flag();
+        // this thread never touches Python, so it may outlive the interpreter
+        thread::spawn(move || {
+            let result = engine.lock().search(&limits, stop, |_| {});
+            let (lock, done) = &*search_slot;
+            *lock.lock().unwrap_or_else(|err| err.into_inner()) = Some(result);
+            done.notify_all();
+        });
+        let future = SearchFuture::create_instance(py, slot)?;
+        if let Some(callback) = callback {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item(py, "target", future.as_object().getattr(py, "_run_callback")?)?;
+            kwargs.set_item(py, "args", PyTuple::new(py, &[callback]))?;
+            let thread = py.import("threading")?.call(py, "Thread", NoArgs, Some(&kwargs))?;
+            thread.call_method(py, "start", NoArgs, None)?;
+        }
+        return Ok(future);
+    }
+
+    // Aborts the running search, which then returns the best move found so far, and the
+    // searches waiting for it. Searches started later are not affected.
+    def stop(&self) -> PyResult<PyObject> {
+        self.engine(py).stop();
+        return Ok(py.None());
+    }
+});
+
+/// Where a background search leaves its result.
+type SearchSlot = Arc<(
+    Mutex<Option<Result<search::SearchResult, PositionError>>>,
+    Condvar,
+)>;
+
+// The result of a search started with `Engine.search_async`.
+py_class!(class SearchFuture |py| {
+    data slot: SearchSlot;
+
+    def done(&self) -> PyResult<bool> {
+        let (lock, _) = &**self.slot(py);
+        return Ok(lock.lock().unwrap_or_else(|err| err.into_inner()).is_some());
+    }
+
+    // Waits for the search to finish and returns the same dict as `Engine.search`.
+    def wait(&self) -> PyResult<PyDict> {
+        let slot = self.slot(py);
+        let result = py.allow_threads(|| {
+            let (lock, done) = &**slot;
+            let mut result = lock.lock().unwrap_or_else(|err| err.into_inner());
+            while result.is_none() {
+                result = done.wait(result).unwrap_or_else(|err| err.into_inner());
+            }
+            return result.clone().unwrap();
+        });
+        let result = result.map_err(|err| position_error(py, err))?;
+        return result_dict(py, &result);
+    }
+
+    def _run_callback(&self, callback: PyObject) -> PyResult<PyObject> {
+        let dict = self.wait(py)?;
+        return callback.call(py, (dict,), None);
+    }
+});
+
+fn value_error(py: Python, msg: &str) -> PyErr {
+    return PyErr::new::<exc::ValueError, _>(py, msg);
+}
+
+fn position_error(py: Python, err: PositionError) -> PyErr {
+    return value_error(py, &err.to_string());
+}
+
+/// The result of a search as a dict with the keys `move`, `score` (centipawns from the
+/// side to move's view, `None` for mate scores), `mate` (moves until mate, negative when
+/// getting mated), `depth`, `nodes`, `time` (milliseconds), `hashfull`, `pv` and `ponder`.
+fn result_dict(py: Python, result: &search::SearchResult) -> PyResult<PyDict> {
+    let dict = PyDict::new(py);
+    let pv: Vec<String> = result.pv.iter().map(|mv| mv.stringify()).collect();
+    dict.set_item(py, "move", result.best_move.stringify())?;
+    dict.set_item(py, "score", score::centipawns(result.score))?;
+    dict.set_item(py, "mate", score::mate_moves(result.score))?;
+    dict.set_item(py, "depth", result.depth)?;
+    dict.set_item(py, "nodes", result.nodes)?;
+    dict.set_item(py, "time", result.elapsed.as_millis() as u64)?;
+    dict.set_item(py, "hashfull", result.hashfull)?;
+    dict.set_item(py, "pv", pv)?;
+    dict.set_item(py, "ponder", result.ponder_move().map(|mv| mv.stringify()))?;
+    return Ok(dict);
+}
+
+/// Searches the position after `moves` from `fen` without holding the GIL.
+/// Engine behind the module-level functions, so they do not allocate a table per call.
+static ENGINE: OnceLock<engine::SharedEngine> = OnceLock::new();
+
+/// Searches the position reached by playing `moves` from `fen`, or from the initial
+/// position when `fen` is `None`, to `depth` plies. Use `Engine` for anything else.
+fn find_best_move(py: Python, moves: &str, depth: u8, fen: Option<&str>) -> PyResult<String> {
+    let shared = ENGINE.get_or_init(|| {
+        let engine = engine::Engine::new(tt::DEFAULT_SIZE_MB);
+        return engine::SharedEngine::new(engine);
+    });
+    let moves = position::parse_moves(moves);
+    let result = py.allow_threads(|| {
+        let mut engine = shared.lock();
+        engine.set_position(fen, &moves)?;
+        let stop = shared.stop_flag();
+        return engine.search(&SearchLimits::depth(depth), stop, |_| {});
+    });
+    let result = result.map_err(|err| position_error(py, err))?;
+    return Ok(result.best_move.stringify());
 }
@@ -1,8 +1,28 @@
+#![allow(clippy::needless_return)]
+mod bench;
+mod engine;
 mod eval;
+mod movepick;
+mod pawns;
+mod position;
+mod score;
 mod search;
+mod see;
+mod timeman;
+mod tt;
+mod uci;
 #[allow(unused)]
 mod utils;
 
 fn main() {
-    utils::play_match(4);
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("bench") {
+        let depth = args
+            .get(2)
+            .and_then(|depth| depth.parse().ok())
+            .unwrap_or(bench::DEFAULT_DEPTH);
+        bench::run(depth, true);
+        return;
+    }
+    uci::run();
 }
@@ -0,0 +1,330 @@
+use pleco::{BitMove, Board, Player};
+
+use crate::search::MAX_PLY;
+use crate::see;
+
+/// History scores are kept within this bound so they never overflow and newer results
+/// still have an effect.
+const MAX_HISTORY: i32 = 16384;
+
+/// Move ordering information collected during a search.
+pub struct Heuristics {
+    /// Two quiet moves per ply that recently caused a beta cutoff.
+    killers: Vec<[BitMove; 2]>,
+    /// How often a quiet move caused a cutoff, indexed by side, from and to square.
+    history: Vec<[[i32; 64]; 64]>,
+    /// The quiet move that refuted the previous move, indexed by its from and to square.
+    counters: Vec<[BitMove; 64]>,
+}
+
+impl Heuristics {
+    pub fn new() -> Heuristics {
+        return Heuristics {
+            killers: vec![[BitMove::null(); 2]; MAX_PLY + 1],
+            history: vec![[[0; 64]; 64]; 2],
+            counters: vec![[BitMove::null(); 64]; 64],
+        };
+    }
+
+    pub fn killers(&self, ply: usize) -> [BitMove; 2] {
+        return self.killers[ply];
+    }
+
+    pub fn history(&self, player: Player, mv: BitMove) -> i32 {
+        return self.history[player as usize][mv.get_src_u8() as usize][mv.get_dest_u8() as usize];
+    }
+
+    pub fn counter(&self, previous: Option<BitMove>) -> BitMove {
+        return match previous {
+            Some(prev) => self.counters[prev.get_src_u8() as usize][prev.get_dest_u8() as usize],
+            None => BitMove::null(),
+        };
+    }
+
+    /// Records a quiet move that caused a beta cutoff. The quiet moves searched before it
+    /// in `tried` failed to do so and are scored down.
+    pub fn update(&mut self, board: &Board, mv: BitMove, tried: &[BitMove], ply: usize, depth: u8) {
+        if self.killers[ply][0] != mv {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = mv;
+        }
+        if let Some(prev) = board.last_move() {
+            self.counters[prev.get_src_u8() as usize][prev.get_dest_u8() as usize] = mv;
+        }
+
+        let bonus = (depth as i32 * depth as i32).min(MAX_HISTORY);
+        let player = board.turn();
+        self.add_history(player, mv, bonus);
+        for quiet in tried {
+            self.add_history(player, *quiet, -bonus);
+        }
+    }
+
+    fn add_history(&mut self, player: Player, mv: BitMove, bonus: i32) {
+        let entry =
+            &mut self.history[player as usize][mv.get_src_u8() as usize][mv.get_dest_u8() as usize];
+        // the more extreme a score already is, the less it changes
+        *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+    }
+}
+
+/// Most valuable victim, least valuable attacker. Promotions count as capturing the
+/// promoted piece.
+pub fn mvv_lva(board: &Board, mv: BitMove) -> i32 {
+    let mut victim = board.captured_piece(mv) as i32;
+    if mv.is_promo() {
+        victim += mv.promo_piece() as i32;
+    }
+    let attacker = board.moved_piece(mv).type_of() as i32;
+    return victim * 8 - attacker;
+}
+
+fn is_tactical(mv: BitMove) -> bool {
+    return mv.is_capture() || mv.is_promo();
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Stage {
+    TtMove,
+    ScoreCaptures,
+    Captures,
+    Killers,
+    ScoreQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Hands out the legal moves of a position one at a time, the most promising first:
+/// the transposition table move, captures by MVV-LVA, killers and the counter-move, and
+/// the remaining quiet moves by their history score. Captures that lose material in the
+/// exchange that follows are only tried at the very end. Moves are only scored once the
+/// search gets to their stage, so a cutoff early on saves the work.
+pub struct MovePicker {
+    stage: Stage,
+    tt_move: BitMove,
+    refutations: [BitMove; 3],
+    refutation_idx: usize,
+    captures: Vec<(BitMove, i32)>,
+    quiets: Vec<(BitMove, i32)>,
+    bad_captures: Vec<BitMove>,
+}
+
+impl MovePicker {
+    /// `moves` must be the legal moves of `board`. The table move and the refutations are
+    /// only tried if they are among them.
+    pub fn new(
+        moves: Vec<BitMove>,
+        tt_move: BitMove,
+        killers: [BitMove; 2],
+        counter: BitMove,
+    ) -> MovePicker {
+        let mut captures = Vec::new();
+        let mut quiets = Vec::new();
+        for mv in moves {
+            if is_tactical(mv) {
+                captures.push((mv, 0));
+            } else {
+                quiets.push((mv, 0));
+            }
+        }
+        let tt_move = match captures
+            .iter()
+            .chain(quiets.iter())
+            .any(|(mv, _)| *mv == tt_move)
+        {
+            true => tt_move,
+            false => BitMove::null(),
+        };
+        return MovePicker {
+            stage: Stage::TtMove,
+            tt_move,
+            refutations: [killers[0], killers[1], counter],
+            refutation_idx: 0,
+            captures,
+            quiets,
+            bad_captures: Vec::new(),
+        };
+    }
+
+    pub fn next(&mut self, board: &Board, heuristics: &Heuristics) -> Option<BitMove> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::ScoreCaptures;
+                    if !self.tt_move.is_null() {
+                        return Some(self.tt_move);
+                    }
+                }
+                Stage::ScoreCaptures => {
+                    for (mv, score) in self.captures.iter_mut() {
+                        *score = mvv_lva(board, *mv);
+                    }
+                    self.stage = Stage::Captures;
+                }
+                Stage::Captures => match pick_best(&mut self.captures, self.tt_move) {
+                    Some(mv) if !see::see(board, mv, 0) => self.bad_captures.push(mv),
+                    Some(mv) => return Some(mv),
+                    None => self.stage = Stage::Killers,
+                },
+                Stage::Killers => {
+                    if self.refutation_idx >= self.refutations.len() {
+                        self.stage = Stage::ScoreQuiets;
+                        continue;
+                    }
+                    let mv = self.refutations[self.refutation_idx];
+                    self.refutation_idx += 1;
+                    if mv.is_null() || mv == self.tt_move {
+                        continue;
+                    }
+                    // only quiet moves of this position, each of them once
+                    if let Some(idx) = self.quiets.iter().position(|(quiet, _)| *quiet == mv) {
+                        self.quiets.swap_remove(idx);
+                        return Some(mv);
+                    }
+                }
+                Stage::ScoreQuiets => {
+                    let player = board.turn();
+                    for (mv, score) in self.quiets.iter_mut() {
+                        *score = heuristics.history(player, *mv);
+                    }
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match pick_best(&mut self.quiets, self.tt_move) {
+                    Some(mv) => return Some(mv),
+                    None => self.stage = Stage::BadCaptures,
+                },
+                Stage::BadCaptures => {
+                    // in the order they were rejected, best MVV-LVA first
+                    if self.bad_captures.is_empty() {
+                        self.stage = Stage::Done;
+                    } else {
+                        return Some(self.bad_captures.remove(0));
+                    }
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+/// Removes and returns the highest scored move, skipping `skip`.
+fn pick_best(moves: &mut Vec<(BitMove, i32)>, skip: BitMove) -> Option<BitMove> {
+    loop {
+        let best = moves
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, score))| *score)
+            .map(|(idx, _)| idx)?;
+        let (mv, _) = moves.swap_remove(best);
+        if mv != skip {
+            return Some(mv);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::find_move;
+
+    fn all_moves(board: &Board, picker: &mut MovePicker, heuristics: &Heuristics) -> Vec<String> {
+        let mut moves = Vec::new();
+        while let Some(mv) = picker.next(board, heuristics) {
+            moves.push(mv.stringify());
+        }
+        return moves;
+    }
+
+    #[test]
+    fn every_move_once() {
+        let board = Board::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+        )
+        .unwrap();
+        let heuristics = Heuristics::new();
+        let tt_move = find_move(&board, "e2a6");
+        let killers = [find_move(&board, "a2a3"), tt_move];
+        let mut picker = MovePicker::new(
+            board.generate_moves().vec(),
+            tt_move,
+            killers,
+            BitMove::null(),
+        );
+        let mut picked = all_moves(&board, &mut picker, &heuristics);
+        assert_eq!(picked[0], "e2a6");
+        picked.sort();
+        let mut legal: Vec<String> = board
+            .generate_moves()
+            .iter()
+            .map(|mv| mv.stringify())
+            .collect();
+        legal.sort();
+        assert_eq!(picked, legal);
+    }
+
+    #[test]
+    fn stage_order() {
+        // the queen on f3 can take the knight on f6 or the pawn on h3
+        let board = Board::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+        )
+        .unwrap();
+        let mut heuristics = Heuristics::new();
+        let killer = find_move(&board, "a2a3");
+        let good_quiet = find_move(&board, "g2g3");
+        heuristics.update(&board, good_quiet, &[], 5, 6);
+        let mut picker = MovePicker::new(
+            board.generate_moves().vec(),
+            BitMove::null(),
+            [killer, BitMove::null()],
+            BitMove::null(),
+        );
+        let picked = all_moves(&board, &mut picker, &heuristics);
+        let position = |uci: &str| picked.iter().position(|mv| mv == uci).unwrap();
+
+        assert!(position("f3f6") < position("f3h3"));
+        assert!(position("g2h3") < position("f3h3"));
+        let captures = board
+            .generate_moves()
+            .iter()
+            .filter(|mv| is_tactical(**mv) && see::see(&board, **mv, 0))
+            .count();
+        assert!(position("e2a6") < captures);
+        assert_eq!(position("a2a3"), captures);
+        assert_eq!(position("g2g3"), captures + 1);
+    }
+
+    #[test]
+    fn bad_captures_last() {
+        // taking the knight with the queen loses it to the pawn on e6
+        let board = Board::from_fen("4k3/8/4p3/3n4/2P5/8/3Q4/4K3 w - - 0 1").unwrap();
+        let heuristics = Heuristics::new();
+        let mut picker = MovePicker::new(
+            board.generate_moves().vec(),
+            BitMove::null(),
+            [BitMove::null(); 2],
+            BitMove::null(),
+        );
+        let picked = all_moves(&board, &mut picker, &heuristics);
+        assert_eq!(picked.first().unwrap(), "c4d5");
+        assert_eq!(picked.last().unwrap(), "d2d5");
+    }
+
+    #[test]
+    fn killers_and_counters() {
+        let mut board = Board::start_pos();
+        board.apply_uci_move("e2e4");
+        let mut heuristics = Heuristics::new();
+        let first = find_move(&board, "e7e5");
+        let second = find_move(&board, "c7c5");
+        heuristics.update(&board, first, &[], 3, 2);
+        heuristics.update(&board, second, &[first], 3, 2);
+        assert_eq!(heuristics.killers(3), [second, first]);
+        assert_eq!(heuristics.counter(board.last_move()), second);
+        assert!(heuristics.history(Player::Black, second) > 0);
+        assert!(
+            heuristics.history(Player::Black, first) < heuristics.history(Player::Black, second)
+        );
+    }
+}
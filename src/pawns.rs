@@ -0,0 +1,290 @@
+use pleco::{Board, PieceType, Player};
+use std::cell::RefCell;
+
+use crate::eval::TaperedScore;
+use crate::score::Score;
+
+/// Entries of the pawn hash table of every search thread.
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+const FILE_H: u64 = FILE_A << 7;
+
+const DOUBLED: TaperedScore = TaperedScore::new(-10, -25);
+const ISOLATED: TaperedScore = TaperedScore::new(-10, -15);
+const BACKWARD: TaperedScore = TaperedScore::new(-8, -12);
+/// Bonus of a pawn defended by or standing next to another pawn, by relative rank.
+const CONNECTED: [Score; 8] = [0, 5, 8, 10, 16, 28, 45, 0];
+/// Bonus of a passed pawn by relative rank.
+const PASSED_MG: [Score; 8] = [0, 5, 10, 15, 30, 50, 80, 0];
+const PASSED_EG: [Score; 8] = [0, 10, 20, 35, 60, 100, 150, 0];
+/// Endgame bonus per square the enemy king is away from the square in front of a passed
+/// pawn, and penalty per square of the own king, multiplied by how advanced the pawn is.
+const KING_DISTANCE_THEIRS: Score = 5;
+const KING_DISTANCE_OURS: Score = 2;
+
+/// The pawn structure terms of a position, which only depend on the pawns.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct PawnEntry {
+    key: u64,
+    score: TaperedScore,
+    /// Passed pawns of white and black.
+    passed: [u64; 2],
+}
+
+/// Caches the pawn structure by pleco's pawn key. Pawns move rarely, so most positions
+/// in a search share their pawn structure with many others.
+struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl PawnTable {
+    fn new(size: usize) -> PawnTable {
+        // an empty entry has the key of a position without pawns and is also correct for it
+        return PawnTable {
+            entries: vec![PawnEntry::default(); size],
+        };
+    }
+
+    fn probe(&mut self, board: &Board) -> PawnEntry {
+        let key = board.pawn_key();
+        let idx = (key % self.entries.len() as u64) as usize;
+        if self.entries[idx].key != key {
+            let white = board.piece_bb(Player::White, PieceType::P).0;
+            let black = board.piece_bb(Player::Black, PieceType::P).0;
+            let (score, passed) = structure(white, black);
+            self.entries[idx] = PawnEntry { key, score, passed };
+        }
+        return self.entries[idx];
+    }
+}
+
+thread_local! {
+    static PAWN_TABLE: RefCell<PawnTable> = RefCell::new(PawnTable::new(PAWN_TABLE_SIZE));
+}
+
+/// Pawn structure evaluation from white's point of view.
+pub fn evaluate(board: &Board) -> TaperedScore {
+    let entry = PAWN_TABLE.with(|table| table.borrow_mut().probe(board));
+    return entry.score + passed_pawns(board, Player::White, entry.passed[0])
+        - passed_pawns(board, Player::Black, entry.passed[1]);
+}
+
+fn east(bb: u64) -> u64 {
+    return (bb << 1) & !FILE_A;
+}
+
+fn west(bb: u64) -> u64 {
+    return (bb >> 1) & !FILE_H;
+}
+
+/// Moves every square one rank towards the opponent of `player`.
+fn forward(bb: u64, player: Player) -> u64 {
+    return match player {
+        Player::White => bb << 8,
+        Player::Black => bb >> 8,
+    };
+}
+
+/// All squares in front of the squares of `bb` as seen by `player`.
+fn front_span(bb: u64, player: Player) -> u64 {
+    let mut span = forward(bb, player);
+    for _ in 0..6 {
+        span |= forward(span, player);
+    }
+    return span;
+}
+
+/// The squares on the files next to `bb` in front of it as seen by `player`, from where
+/// enemy pawns attack it or can do so once they advance.
+pub fn attack_span(bb: u64, player: Player) -> u64 {
+    let front = front_span(bb, player);
+    return east(front) | west(front);
+}
+
+/// The squares attacked by the pawns of `player` in `pawns`.
+pub fn pawn_attacks(pawns: u64, player: Player) -> u64 {
+    let front = forward(pawns, player);
+    return east(front) | west(front);
+}
+
+fn relative_rank(sq: u32, player: Player) -> usize {
+    let rank = (sq / 8) as usize;
+    return match player {
+        Player::White => rank,
+        Player::Black => 7 - rank,
+    };
+}
+
+/// Evaluates the pawns of one side. Returns the score and its passed pawns.
+fn side_structure(own: u64, enemy: u64, player: Player) -> (TaperedScore, u64) {
+    let them = !player;
+    let enemy_attacks = pawn_attacks(enemy, them);
+    let mut score = TaperedScore::default();
+    let mut passed = 0;
+    let mut pawns = own;
+    while pawns != 0 {
+        let sq = pawns.trailing_zeros();
+        pawns &= pawns - 1;
+        let bb = 1u64 << sq;
+        let rank = relative_rank(sq, player);
+        let file = FILE_A << (sq % 8);
+        let adjacent = east(file) | west(file);
+        let front = front_span(bb, player);
+
+        let isolated = own & adjacent == 0;
+        let doubled = own & front != 0;
+        let supported = pawn_attacks(bb, them) & own != 0;
+        let phalanx = (east(bb) | west(bb)) & own != 0;
+        // no pawn next to it or behind it on the adjacent files can ever defend it, and it
+        // cannot advance safely
+        let ahead = attack_span(bb, player);
+        let backward =
+            !isolated && own & adjacent & !ahead == 0 && forward(bb, player) & enemy_attacks != 0;
+
+        if doubled {
+            score = score + DOUBLED;
+        }
+        if isolated {
+            score = score + ISOLATED;
+        } else if backward {
+            score = score + BACKWARD;
+        }
+        if supported || phalanx {
+            let bonus = CONNECTED[rank];
+            score = score + TaperedScore::new(bonus, bonus / 2);
+        }
+        // only the front pawn of doubled pawns counts as passed
+        if !doubled && enemy & (front | ahead) == 0 {
+            passed |= bb;
+            score = score + TaperedScore::new(PASSED_MG[rank], PASSED_EG[rank]);
+        }
+    }
+    return (score, passed);
+}
+
+/// The pawn structure score from white's point of view and the passed pawns of both
+/// sides, from the pawn bitboards alone.
+fn structure(white: u64, black: u64) -> (TaperedScore, [u64; 2]) {
+    let (white_score, white_passed) = side_structure(white, black, Player::White);
+    let (black_score, black_passed) = side_structure(black, white, Player::Black);
+    return (white_score - black_score, [white_passed, black_passed]);
+}
+
+fn distance(a: u32, b: u32) -> Score {
+    let files = (a % 8) as Score - (b % 8) as Score;
+    let ranks = (a / 8) as Score - (b / 8) as Score;
+    return files.abs().max(ranks.abs());
+}
+
+/// The terms of `player`'s passed pawns that depend on more than the pawns: the distance
+/// of both kings to the square in front of the pawn and whether an enemy piece blocks it.
+fn passed_pawns(board: &Board, player: Player, passed: u64) -> TaperedScore {
+    let own_king = board.king_sq(player).0 as u32;
+    let their_king = board.king_sq(!player).0 as u32;
+    let enemies = board.get_occupied_player(!player).0;
+    let mut score = TaperedScore::default();
+    let mut pawns = passed;
+    while pawns != 0 {
+        let sq = pawns.trailing_zeros();
+        pawns &= pawns - 1;
+        let rank = relative_rank(sq, player);
+        let stop = forward(1u64 << sq, player);
+        if stop == 0 {
+            continue;
+        }
+        let stop_sq = stop.trailing_zeros();
+
+        let weight = rank as Score - 2;
+        if weight > 0 {
+            let proximity = KING_DISTANCE_THEIRS * distance(their_king, stop_sq)
+                - KING_DISTANCE_OURS * distance(own_king, stop_sq);
+            score = score + TaperedScore::new(0, proximity * weight);
+        }
+        if stop & enemies != 0 {
+            score = score - TaperedScore::new(PASSED_MG[rank] / 2, PASSED_EG[rank] / 2);
+        }
+    }
+    return score;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pawns(board: &Board) -> (u64, u64) {
+        return (
+            board.piece_bb(Player::White, PieceType::P).0,
+            board.piece_bb(Player::Black, PieceType::P).0,
+        );
+    }
+
+    fn white_structure(fen: &str) -> (TaperedScore, u64) {
+        let board = Board::from_fen(fen).unwrap();
+        let (white, black) = pawns(&board);
+        return side_structure(white, black, Player::White);
+    }
+
+    #[test]
+    fn symmetric() {
+        let (white, black) = pawns(&Board::start_pos());
+        assert_eq!(structure(white, black).0, TaperedScore::default());
+    }
+
+    #[test]
+    fn doubled_and_isolated() {
+        // two isolated pawns on the c-file, the rear one doubled
+        let (score, passed) = white_structure("4k3/8/8/8/2P5/2P5/8/4K3 w - - 0 1");
+        assert_eq!(score, DOUBLED + ISOLATED * 2 + TaperedScore::new(15, 35));
+        assert_eq!(passed, 1 << 26);
+    }
+
+    #[test]
+    fn passed_pawns_by_rank() {
+        let (score, passed) = white_structure("4k3/1P6/8/8/8/8/p7/4K3 w - - 0 1");
+        assert_eq!(passed, 1 << 49);
+        assert_eq!(score, ISOLATED + TaperedScore::new(80, 150));
+        // a pawn on an adjacent file in front stops it from being passed
+        let (_, passed) = white_structure("4k3/8/p7/8/1P6/8/8/4K3 w - - 0 1");
+        assert_eq!(passed, 0);
+    }
+
+    #[test]
+    fn connected() {
+        let (score, _) = white_structure("4k3/pp6/8/8/8/8/1PP5/4K3 w - - 0 1");
+        let bonus = CONNECTED[1];
+        assert_eq!(score, TaperedScore::new(bonus, bonus / 2) * 2);
+    }
+
+    #[test]
+    fn backward() {
+        // the d-pawn lags behind the c-pawn and e5 controls its stop square
+        let (score, _) = white_structure("4k3/3p4/8/2P1p3/8/3P4/8/4K3 w - - 0 1");
+        let (without, _) = white_structure("4k3/3p4/8/2P5/8/3P4/8/4K3 w - - 0 1");
+        assert_eq!(score - without, BACKWARD);
+    }
+
+    #[test]
+    fn king_proximity_and_blockade() {
+        let near = Board::from_fen("7k/8/3PK3/8/8/8/8/8 w - - 0 1").unwrap();
+        let far = Board::from_fen("3k4/8/3P4/8/8/8/8/7K w - - 0 1").unwrap();
+        assert!(evaluate(&near).eg > evaluate(&far).eg);
+
+        let free = Board::from_fen("4k3/8/3P4/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let blocked = Board::from_fen("4k3/3n4/3P4/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let difference = evaluate(&free) - evaluate(&blocked);
+        assert_eq!(difference.mg, PASSED_MG[5] / 2);
+    }
+
+    #[test]
+    fn table_caches_structure() {
+        let mut table = PawnTable::new(64);
+        let board = Board::from_fen("4k3/8/3P4/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let entry = table.probe(&board);
+        assert_eq!(entry.key, board.pawn_key());
+        assert_eq!(table.probe(&board), entry);
+        // a different king position keeps the pawn entry
+        let moved = Board::from_fen("4k3/8/3P4/8/8/8/8/3K4 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&moved), entry);
+    }
+}
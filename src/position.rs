@@ -0,0 +1,187 @@
+use pleco::Board;
+use std::error::Error;
+use std::fmt;
+use std::panic;
+
+/// Why a position could not be set up from a FEN and a list of moves.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionError {
+    InvalidFen(String),
+    /// The move is not written in UCI notation, e.g. `e2e4` or `e7e8q`.
+    MalformedMove(String),
+    /// The move is well formed but not legal in the position it was played in.
+    IllegalMove(String),
+    /// The side to move is checkmated or stalemated, so there is no position left to
+    /// search.
+    NoPosition,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            PositionError::InvalidFen(fen) => write!(f, "invalid fen: {}", fen),
+            PositionError::MalformedMove(mv) => write!(f, "malformed move: {:?}", mv),
+            PositionError::IllegalMove(mv) => write!(f, "illegal move: {}", mv),
+            PositionError::NoPosition => write!(f, "no position to search, the game is over"),
+        };
+    }
+}
+
+impl Error for PositionError {}
+
+/// Splits a space separated list of UCI moves, an empty or blank string is no moves.
+/// Only the Python module takes moves as one string.
+#[allow(dead_code)]
+pub fn parse_moves(uci_moves: &str) -> Vec<&str> {
+    return uci_moves.split_whitespace().collect();
+}
+
+/// Checks that `mv` looks like a UCI move, without looking at any position.
+pub fn is_uci_move(mv: &str) -> bool {
+    let bytes = mv.as_bytes();
+    if bytes.len() != 4 && bytes.len() != 5 {
+        return false;
+    }
+    let square =
+        |file: u8, rank: u8| (b'a'..=b'h').contains(&file) && (b'1'..=b'8').contains(&rank);
+    if !square(bytes[0], bytes[1]) || !square(bytes[2], bytes[3]) {
+        return false;
+    }
+    return bytes.len() == 4 || b"qrbn".contains(&bytes[4]);
+}
+
+/// Checks the piece placement of a FEN: 8 ranks of 8 squares, one king per side and no
+/// pawns on the first or last rank. pleco panics on some of these instead of failing.
+fn is_valid_placement(fen: &str) -> bool {
+    let placement = fen.split_whitespace().next().unwrap_or("");
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return false;
+    }
+    for (idx, rank) in ranks.iter().enumerate() {
+        let mut files = 0;
+        for c in rank.chars() {
+            match c {
+                '1'..='8' => files += c as u32 - '0' as u32,
+                'p' | 'P' if idx == 0 || idx == 7 => return false,
+                'p' | 'n' | 'b' | 'r' | 'q' | 'k' | 'P' | 'N' | 'B' | 'R' | 'Q' | 'K' => files += 1,
+                _ => return false,
+            }
+        }
+        if files != 8 {
+            return false;
+        }
+    }
+    return placement.matches('K').count() == 1 && placement.matches('k').count() == 1;
+}
+
+/// Parses a FEN, also turning a panic of pleco on a position it cannot handle into an
+/// error. The side that just moved must not be left in check, the search cannot handle
+/// that either.
+fn parse_fen(fen: &str) -> Result<Board, PositionError> {
+    let invalid = || PositionError::InvalidFen(fen.to_string());
+    if !is_valid_placement(fen) {
+        return Err(invalid());
+    }
+    let board = match panic::catch_unwind(|| Board::from_fen(fen)) {
+        Ok(Ok(board)) => board,
+        _ => return Err(invalid()),
+    };
+    let king = board.king_sq(!board.turn());
+    let checkers =
+        board.attackers_to(king, board.occupied()) & board.get_occupied_player(board.turn());
+    if checkers.is_not_empty() {
+        return Err(invalid());
+    }
+    return Ok(board);
+}
+
+/// Sets up a board like UCI `position fen <fen> moves <moves>`, starting from the initial
+/// position when `fen` is `None`.
+pub fn setup(fen: Option<&str>, moves: &[&str]) -> Result<Board, PositionError> {
+    let mut board = match fen {
+        Some(fen) => parse_fen(fen)?,
+        None => Board::start_pos(),
+    };
+    for mv in moves {
+        play(&mut board, mv)?;
+    }
+    return Ok(board);
+}
+
+/// Plays a single UCI move, leaving the board untouched if it is malformed or illegal.
+pub fn play(board: &mut Board, mv: &str) -> Result<(), PositionError> {
+    if !is_uci_move(mv) {
+        return Err(PositionError::MalformedMove(mv.to_string()));
+    }
+    if !board.apply_uci_move(mv) {
+        return Err(PositionError::IllegalMove(mv.to_string()));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uci_moves() {
+        assert!(is_uci_move("e2e4"));
+        assert!(is_uci_move("e7e8q"));
+        assert!(!is_uci_move(""));
+        assert!(!is_uci_move("e2"));
+        assert!(!is_uci_move("e2e9"));
+        assert!(!is_uci_move("e7e8k"));
+        assert!(!is_uci_move("Nf3"));
+    }
+
+    #[test]
+    fn empty_move_list() {
+        assert!(parse_moves("").is_empty());
+        assert!(parse_moves("  ").is_empty());
+        assert_eq!(parse_moves(" e2e4  e7e5 "), vec!["e2e4", "e7e5"]);
+        let board = setup(None, &parse_moves("")).unwrap();
+        assert_eq!(board.fen(), Board::start_pos().fen());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            setup(Some("not a fen"), &[]).err(),
+            Some(PositionError::InvalidFen("not a fen".to_string()))
+        );
+        // pleco panics on these instead of returning an error
+        for fen in [
+            "8/8/8/8/8/8/8/8 w - - 0 1",
+            "4k3/8/8/8/8/8/8/8 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3KK3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K2 w - - 0 1",
+            "4k3/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K2P w - - 0 1",
+            "4k3/4R3/8/8/8/8/8/4K3 w - - 0 1",
+        ] {
+            assert_eq!(
+                setup(Some(fen), &[]).err(),
+                Some(PositionError::InvalidFen(fen.to_string()))
+            );
+        }
+        assert_eq!(
+            setup(None, &["e2e4", "e7e5", "e4e5"]).err(),
+            Some(PositionError::IllegalMove("e4e5".to_string()))
+        );
+        assert_eq!(
+            setup(None, &["e2e4", "xyz"]).err(),
+            Some(PositionError::MalformedMove("xyz".to_string()))
+        );
+    }
+
+    #[test]
+    fn play_keeps_board_on_error() {
+        let mut board = Board::start_pos();
+        assert!(play(&mut board, "e2e5").is_err());
+        assert!(play(&mut board, "").is_err());
+        assert_eq!(board.ply(), 0);
+        assert!(play(&mut board, "e2e4").is_ok());
+        assert_eq!(board.ply(), 1);
+    }
+}
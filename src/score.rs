@@ -0,0 +1,115 @@
+use crate::search::MAX_PLY;
+
+/// A score in centipawns from the point of view of the side to move. Mates are encoded
+/// as `MATE` minus the number of plies from the root of the search to the mate.
+pub type Score = i32;
+
+/// Bound on every score, used for the initial search window.
+pub const INFINITE: Score = 10000;
+/// Score of giving checkmate at the root.
+pub const MATE: Score = 9999;
+/// Scores beyond this are mates found within the search.
+pub const MATE_BOUND: Score = MATE - MAX_PLY as Score;
+
+/// Score for the side to move when it mates in `ply` plies from the root.
+pub fn mate_in(ply: usize) -> Score {
+    return MATE - ply as Score;
+}
+
+/// Score for the side to move when it gets mated in `ply` plies from the root.
+pub fn mated_in(ply: usize) -> Score {
+    return -MATE + ply as Score;
+}
+
+pub fn is_mate(score: Score) -> bool {
+    return score.abs() >= MATE_BOUND;
+}
+
+/// Mate scores in the transposition table count the plies from the stored position
+/// instead of from the root, so they stay correct when reached through another path.
+pub fn to_tt(score: Score, ply: usize) -> Score {
+    if score >= MATE_BOUND {
+        return score + ply as Score;
+    }
+    if score <= -MATE_BOUND {
+        return score - ply as Score;
+    }
+    return score;
+}
+
+/// Reverses `to_tt` for a position `ply` plies from the root.
+pub fn from_tt(score: Score, ply: usize) -> Score {
+    if score >= MATE_BOUND {
+        return score - ply as Score;
+    }
+    if score <= -MATE_BOUND {
+        return score + ply as Score;
+    }
+    return score;
+}
+
+/// Moves until mate for a score at the root, negative when the side to move gets mated.
+pub fn mate_moves(score: Score) -> Option<i32> {
+    if !is_mate(score) {
+        return None;
+    }
+    let plies = MATE - score.abs();
+    let moves = (plies + 1) / 2;
+    return Some(if score > 0 { moves } else { -moves });
+}
+
+/// The score in centipawns, `None` for mate scores. Only the Python module reports
+/// scores this way.
+#[allow(dead_code)]
+pub fn centipawns(score: Score) -> Option<i32> {
+    if is_mate(score) {
+        return None;
+    }
+    return Some(score);
+}
+
+/// Formats a score at the root for the UCI `info` command, e.g. `cp 35` or `mate -2`.
+/// Unused by the Python module.
+#[allow(dead_code)]
+pub fn to_uci(score: Score) -> String {
+    return match mate_moves(score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", score),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mate_moves_from_root() {
+        assert_eq!(mate_moves(mate_in(1)), Some(1));
+        assert_eq!(mate_moves(mate_in(3)), Some(2));
+        assert_eq!(mate_moves(mated_in(4)), Some(-2));
+        assert_eq!(mate_moves(350), None);
+        assert!(is_mate(mated_in(MAX_PLY)));
+        assert!(!is_mate(MATE_BOUND - 1));
+    }
+
+    #[test]
+    fn tt_adjustment() {
+        // mate in 2 plies from a node at ply 3 is mate in 5 from the root
+        let stored = to_tt(mate_in(5), 3);
+        assert_eq!(stored, mate_in(2));
+        assert_eq!(from_tt(stored, 3), mate_in(5));
+        // reached at ply 1 through a transposition it is mate in 3 from the root
+        assert_eq!(from_tt(stored, 1), mate_in(3));
+        assert_eq!(from_tt(to_tt(mated_in(6), 2), 4), mated_in(8));
+        assert_eq!(to_tt(120, 7), 120);
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(to_uci(-35), "cp -35");
+        assert_eq!(to_uci(mate_in(3)), "mate 2");
+        assert_eq!(to_uci(mated_in(2)), "mate -1");
+        assert_eq!(centipawns(120), Some(120));
+        assert_eq!(centipawns(mated_in(2)), None);
+    }
+}
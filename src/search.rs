@@ -1,49 +1,400 @@
-use pleco::{BitMove, Board, Player};
-use std::collections::HashMap;
+use pleco::{BitMove, Board, PieceType, Player};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::eval;
+use crate::movepick::{Heuristics, MovePicker};
+use crate::score::{self, Score, INFINITE};
+use crate::see;
+use crate::timeman::{SearchLimits, TimeManager};
+use crate::tt::{self, MoveType, TranspositionTable};
 
-#[derive(PartialEq, Debug)]
-enum MoveType {
-    Exact,
-    UpperBound,
-    LowerBound,
+pub const MAX_DEPTH: u8 = 64;
+/// Upper bound on the distance from the root of any node in the main search.
+pub const MAX_PLY: usize = 128;
+/// How many nodes are searched between two checks of the clock.
+const NODES_BETWEEN_TIME_CHECKS: u64 = 1024;
+/// Half the width of the first aspiration window around the previous iteration's score.
+const ASPIRATION_WINDOW: Score = 50;
+/// Iterations below this depth are searched with a full window.
+const ASPIRATION_MIN_DEPTH: u8 = 4;
+
+/// The outcome of the last completed iteration of a search.
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub score: Score,
+    pub best_move: BitMove,
+    /// The principal variation, starting with `best_move`.
+    pub pv: Vec<BitMove>,
+    pub depth: u8,
+    pub nodes: u64,
+    /// Permille of the transposition table filled by this search.
+    pub hashfull: usize,
+    pub elapsed: Duration,
 }
 
-#[derive(Debug)]
-pub struct TransitionEntry {
-    value: f32,
-    mv: BitMove,
-    depth: u8,
-    move_type: MoveType,
+impl SearchResult {
+    /// The reply we expect from the opponent, the second move of the PV.
+    pub fn ponder_move(&self) -> Option<BitMove> {
+        return self.pv.get(1).copied();
+    }
+}
+
+/// Largest contempt accepted from the outside, in centipawns.
+pub const MAX_CONTEMPT: i32 = 1000;
+
+/// Most search threads accepted from the outside.
+pub const MAX_THREADS: usize = 256;
+
+/// Settings of the search that are kept between moves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchParams {
+    /// How many centipawns a draw is worth less than an equal position for the side to
+    /// move at the root. Positive values make the engine avoid draws.
+    pub contempt: i32,
+    /// Depth reduction of the null move search.
+    pub null_move_reduction: i32,
+    /// Late move reductions apply from this depth on ...
+    pub lmr_min_depth: i32,
+    /// ... to moves after this many have been searched.
+    pub lmr_min_moves: i32,
+    /// The reduction is `base + ln(depth) * ln(moves) / divisor`, both in hundredths.
+    pub lmr_base: i32,
+    pub lmr_divisor: i32,
+    /// Up to this depth quiet moves are skipped when the static evaluation plus
+    /// `futility_margin` per ply of depth does not reach alpha.
+    pub futility_depth: i32,
+    pub futility_margin: i32,
+    /// Up to this depth a node is cut off when the static evaluation minus
+    /// `reverse_futility_margin` per ply of depth is still above beta.
+    pub reverse_futility_depth: i32,
+    pub reverse_futility_margin: i32,
+    /// Up to this depth a node drops into quiescence when the static evaluation plus
+    /// `razor_margin` per ply of depth is below alpha.
+    pub razor_depth: i32,
+    pub razor_margin: i32,
+    /// Up to this depth only the first `lmp_base + depth * depth` quiet moves are searched.
+    pub lmp_depth: i32,
+    pub lmp_base: i32,
+    /// From this depth on the table move is extended when every other move fails low
+    /// against its value minus `singular_margin` per ply of depth.
+    pub singular_min_depth: i32,
+    pub singular_margin: i32,
+    /// Most plies a single line from the root can be extended by.
+    pub max_extensions: i32,
+    /// Whether the first ply of quiescence also searches quiet moves that give check.
+    pub quiescence_checks: bool,
+    /// Number of threads searching the position together.
+    pub threads: usize,
+}
+
+impl Default for SearchParams {
+    fn default() -> SearchParams {
+        return SearchParams {
+            contempt: 0,
+            null_move_reduction: 2,
+            lmr_min_depth: 3,
+            lmr_min_moves: 3,
+            lmr_base: 75,
+            lmr_divisor: 225,
+            futility_depth: 3,
+            futility_margin: 120,
+            reverse_futility_depth: 6,
+            reverse_futility_margin: 90,
+            razor_depth: 2,
+            razor_margin: 300,
+            lmp_depth: 4,
+            lmp_base: 3,
+            singular_min_depth: 6,
+            singular_margin: 2,
+            max_extensions: 8,
+            quiescence_checks: false,
+            threads: 1,
+        };
+    }
 }
 
-fn move_value(mv: &BitMove, board: &Board) -> u32 {
-    let mut score: u32 = 0;
-    if board.gives_check(*mv) {
-        score += 50;
-    } else if board.is_capture(*mv) {
-        score += 10;
+/// Name, minimum and maximum of the search parameters that can be tuned as options.
+pub const TUNABLE_PARAMS: [(&str, i32, i32); 16] = [
+    ("NullMoveReduction", 1, 6),
+    ("LmrMinDepth", 1, 32),
+    ("LmrMinMoves", 1, 64),
+    ("LmrBase", 0, 300),
+    ("LmrDivisor", 50, 1000),
+    ("FutilityDepth", 0, 16),
+    ("FutilityMargin", 0, 1000),
+    ("ReverseFutilityDepth", 0, 16),
+    ("ReverseFutilityMargin", 0, 1000),
+    ("RazorDepth", 0, 16),
+    ("RazorMargin", 0, 1000),
+    ("LmpDepth", 0, 16),
+    ("LmpBase", 0, 64),
+    ("SingularMinDepth", 1, 64),
+    ("SingularMargin", 0, 100),
+    ("MaxExtensions", 0, 32),
+];
+
+impl SearchParams {
+    fn tunable_mut(&mut self, name: &str) -> Option<&mut i32> {
+        return match name.to_ascii_lowercase().as_str() {
+            "nullmovereduction" => Some(&mut self.null_move_reduction),
+            "lmrmindepth" => Some(&mut self.lmr_min_depth),
+            "lmrminmoves" => Some(&mut self.lmr_min_moves),
+            "lmrbase" => Some(&mut self.lmr_base),
+            "lmrdivisor" => Some(&mut self.lmr_divisor),
+            "futilitydepth" => Some(&mut self.futility_depth),
+            "futilitymargin" => Some(&mut self.futility_margin),
+            "reversefutilitydepth" => Some(&mut self.reverse_futility_depth),
+            "reversefutilitymargin" => Some(&mut self.reverse_futility_margin),
+            "razordepth" => Some(&mut self.razor_depth),
+            "razormargin" => Some(&mut self.razor_margin),
+            "lmpdepth" => Some(&mut self.lmp_depth),
+            "lmpbase" => Some(&mut self.lmp_base),
+            "singularmindepth" => Some(&mut self.singular_min_depth),
+            "singularmargin" => Some(&mut self.singular_margin),
+            "maxextensions" => Some(&mut self.max_extensions),
+            _ => None,
+        };
+    }
+
+    /// The value of one of the `TUNABLE_PARAMS`, names are case insensitive.
+    pub fn tunable(&self, name: &str) -> Option<i32> {
+        let mut params = *self;
+        return params.tunable_mut(name).map(|value| *value);
+    }
+
+    /// Sets one of the `TUNABLE_PARAMS`, clamped to its range. Unknown names are ignored.
+    pub fn set_tunable(&mut self, name: &str, value: i64) {
+        let range = TUNABLE_PARAMS
+            .iter()
+            .find(|(tunable, _, _)| tunable.eq_ignore_ascii_case(name));
+        if let (Some((_, min, max)), Some(param)) = (range, self.tunable_mut(name)) {
+            *param = value.clamp(*min as i64, *max as i64) as i32;
+        }
+    }
+
+    /// Late move reduction for the `moves_searched + 1`th move at `depth`.
+    fn reduction(&self, depth: u8, moves_searched: i32) -> i32 {
+        let scaled = (depth as f64).ln() * (moves_searched as f64).ln() * 10000.0
+            / self.lmr_divisor.max(1) as f64;
+        return (self.lmr_base + scaled as i32) / 100;
     }
-    return score;
 }
 
+/// State shared by all nodes of a single search.
+pub struct SearchInfo<'a> {
+    pub transition_table: &'a TranspositionTable,
+    pub evaluator: fn(&Board) -> Score,
+    /// Set from the outside (or by the search itself when the deadline passes) to abort.
+    pub stop: Arc<AtomicBool>,
+    pub deadline: Option<Instant>,
+    pub nodes: u64,
+    /// Helper threads add their nodes here every few nodes so the main thread can report
+    /// the total while searching.
+    shared_nodes: Option<Arc<AtomicU64>>,
+    pub params: SearchParams,
+    /// Color of the side to move at the root, draws are scored from its point of view.
+    pub root_color: i8,
+    /// Keys of the positions played before the current node, the game before the root
+    /// followed by the search path. Null moves are stored as 0.
+    history: Vec<u64>,
+    /// Number of entries in `history` that were played before the root.
+    game_length: usize,
+    /// Triangular PV table, `pv[ply]` holds the best line found from `ply` onwards.
+    pv: Vec<Vec<BitMove>>,
+    /// Plies the path from the root to `ply` has been extended by.
+    extensions: Vec<i32>,
+    /// Move left out at `ply` while testing whether the table move is singular.
+    excluded: Vec<BitMove>,
+    /// Killer, history and counter-move tables used to order quiet moves.
+    heuristics: Box<Heuristics>,
+}
+
+impl<'a> SearchInfo<'a> {
+    pub fn new(
+        transition_table: &'a TranspositionTable,
+        evaluator: fn(&Board) -> Score,
+    ) -> SearchInfo<'a> {
+        return SearchInfo {
+            transition_table,
+            evaluator,
+            stop: Arc::new(AtomicBool::new(false)),
+            deadline: None,
+            nodes: 0,
+            shared_nodes: None,
+            params: SearchParams::default(),
+            root_color: 1,
+            history: Vec::new(),
+            game_length: 0,
+            pv: vec![Vec::new(); MAX_PLY + 1],
+            extensions: vec![0; MAX_PLY + 2],
+            excluded: vec![BitMove::null(); MAX_PLY + 1],
+            heuristics: Box::new(Heuristics::new()),
+        };
+    }
+
+    /// The principal variation of the last search started at the root.
+    pub fn principal_variation(&self) -> Vec<BitMove> {
+        return self.pv[0].clone();
+    }
+
+    fn update_pv(&mut self, ply: usize, mv: BitMove) {
+        let (head, tail) = self.pv.split_at_mut(ply + 1);
+        head[ply].clear();
+        head[ply].push(mv);
+        head[ply].extend_from_slice(&tail[0]);
+    }
+
+    /// Counts the node and checks whether the search has to be aborted.
+    fn visit_node(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODES_BETWEEN_TIME_CHECKS) {
+            if let Some(shared) = &self.shared_nodes {
+                shared.fetch_add(NODES_BETWEEN_TIME_CHECKS, Ordering::Relaxed);
+            }
+            if let Some(deadline) = self.deadline {
+                if Instant::now() >= deadline {
+                    self.stop.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+        return self.stopped();
+    }
+
+    pub fn stopped(&self) -> bool {
+        return self.stop.load(Ordering::Relaxed);
+    }
+
+    /// Sets the positions that were played before the root, oldest first.
+    pub fn set_game_history(&mut self, keys: Vec<u64>) {
+        self.game_length = keys.len();
+        self.history = keys;
+    }
+
+    /// A position repeated inside the search is scored as a draw right away, one from
+    /// before the root only when it is the third occurrence.
+    fn is_repetition(&self, board: &Board, key: u64) -> bool {
+        let mut occurrences = 0;
+        let window = (board.rule_50().max(0) as usize).min(self.history.len());
+        for distance in 1..=window {
+            let idx = self.history.len() - distance;
+            // positions before a null move can not be repeated
+            if self.history[idx] == 0 {
+                return false;
+            }
+            if distance % 2 == 0 && self.history[idx] == key {
+                if idx >= self.game_length {
+                    return true;
+                }
+                occurrences += 1;
+                if occurrences >= 2 {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+
+    /// The score of a draw for `color`, including contempt.
+    fn draw_score(&self, color: i8) -> Score {
+        let contempt = self.params.contempt as Score;
+        return if color == self.root_color {
+            -contempt
+        } else {
+            contempt
+        };
+    }
+}
+
+/// Neither side has enough material left to give mate.
+fn insufficient_material(board: &Board) -> bool {
+    let mut minors = 0;
+    for player in [Player::White, Player::Black] {
+        for piece in [PieceType::P, PieceType::R, PieceType::Q] {
+            if board.count_piece(player, piece) > 0 {
+                return false;
+            }
+        }
+        minors += board.count_piece(player, PieceType::N) + board.count_piece(player, PieceType::B);
+    }
+    return minors <= 1;
+}
+
+/// Draws by the fifty-move rule, insufficient material or repetition.
+fn is_draw(board: &Board, key: u64, info: &SearchInfo) -> bool {
+    if board.rule_50() >= 100 && !board.checkmate() {
+        return true;
+    }
+    return insufficient_material(board) || info.is_repetition(board, key);
+}
+
+/// Keys of the positions that led to `board`, oldest first. Only positions since the last
+/// capture or pawn move can be repeated. pleco can only take back moves that were played
+/// on this board or the board it was `parallel_clone`d from.
+pub fn game_history(board: &Board) -> Vec<u64> {
+    let mut board = board.parallel_clone();
+    let mut keys = Vec::new();
+    let plies = (board.rule_50().max(0) as u16)
+        .min(board.ply())
+        .min(board.depth());
+    for _ in 0..plies {
+        board.undo_move();
+        keys.push(tt::position_key(&board));
+    }
+    keys.reverse();
+    return keys;
+}
+
+/// Returns a null move with a score of 0 if the search was aborted, callers have to
+/// check `info.stopped()` before trusting the result.
+#[allow(clippy::too_many_arguments)]
 pub fn nega_max(
     mut board: Board,
     depth: u8,
     color: i8,
-    mut alpha: f32,
-    mut beta: f32,
-    transition_table: &mut HashMap<u64, TransitionEntry>,
-    root: bool,
-    evaluator: fn(&Board) -> f32,
+    mut alpha: Score,
+    mut beta: Score,
+    info: &mut SearchInfo,
+    ply: usize,
     do_null: bool,
-) -> (f32, BitMove) {
+) -> (Score, BitMove) {
+    if info.visit_node() {
+        return (0, BitMove::null());
+    }
+    info.pv[ply].clear();
+    let root = ply == 0;
+    let hash = tt::position_key(&board);
+    if !root {
+        if is_draw(&board, hash, info) {
+            return (info.draw_score(color), BitMove::null());
+        }
+        // mate distance pruning, no line from here beats a shorter mate found before
+        alpha = alpha.max(score::mated_in(ply));
+        beta = beta.min(score::mate_in(ply + 1));
+        if alpha >= beta {
+            return (alpha, BitMove::null());
+        }
+    }
     let alpha_original = alpha;
-    let hash = board.zobrist();
+    // nodes searched with a full window, the table must not cut their line short
+    let pv_node = beta - alpha > 1;
     let mut skip_cache = false;
-    let R = 2; //search depth reduction in null move pruning
     let mut moves = board.generate_moves().vec();
+    if root {
+        info.extensions[0] = 0;
+    }
+    // the table entry belongs to the whole node, not to the node without the excluded move
+    let excluded = info.excluded[ply];
+    let singular_search = !excluded.is_null();
+    if singular_search {
+        moves.retain(|mv| *mv != excluded);
+        skip_cache = true;
+        if moves.is_empty() {
+            return (alpha, BitMove::null());
+        }
+    }
 
     if root {
         let mut temp_moves: Vec<BitMove> = Vec::new();
@@ -54,40 +405,105 @@ pub fn nega_max(
             }
             board.undo_move();
         }
-        if temp_moves.len() <= (&moves).len() && temp_moves.len() > 0 {
+        if temp_moves.len() <= moves.len() && !temp_moves.is_empty() {
             moves = temp_moves;
             skip_cache = true;
         }
     }
 
-    match transition_table.get(&hash) {
-        Some(entry) => {
-            if !root && !skip_cache && entry.depth >= depth {
-                //use transition table value
-                if entry.move_type == MoveType::Exact {
-                    return (entry.value, entry.mv);
-                } else if entry.move_type == MoveType::UpperBound {
-                    beta = beta.min(entry.value);
-                } else if entry.move_type == MoveType::LowerBound {
-                    alpha = alpha.max(entry.value);
+    let mut tt_move = BitMove::null();
+    let tt_entry = info.transition_table.probe(hash);
+    if let Some(entry) = tt_entry {
+        tt_move = entry.mv;
+        if !root && !pv_node && !skip_cache && entry.depth >= depth {
+            //use transition table value
+            let value = score::from_tt(entry.value, ply);
+            if entry.move_type == MoveType::Exact {
+                // the line below this node is lost, keep at least the stored move
+                if !entry.mv.is_null() {
+                    info.pv[ply].push(entry.mv);
                 }
+                return (value, entry.mv);
+            } else if entry.move_type == MoveType::UpperBound {
+                beta = beta.min(value);
+            } else if entry.move_type == MoveType::LowerBound {
+                alpha = alpha.max(value);
+            }
 
-                if alpha >= beta {
-                    return (entry.value, entry.mv);
-                }
+            if alpha >= beta {
+                return (value, entry.mv);
             }
         }
-        None => {}
-    };
+    }
+
+    if moves.is_empty() {
+        let score = if board.in_check() {
+            score::mated_in(ply)
+        } else {
+            info.draw_score(color)
+        };
+        return (score, BitMove::null());
+    }
 
-    moves.sort_by(|a, b| move_value(b, &board).cmp(&move_value(a, &board)));
-    if depth == 0 || board.checkmate() || moves.is_empty() {
+    if depth == 0 || ply >= MAX_PLY {
         return (
-            quiesce(board, color, alpha, beta, 10, evaluator),
+            quiesce(
+                board,
+                color,
+                alpha,
+                beta,
+                info.params.quiescence_checks,
+                info,
+                ply,
+            ),
             BitMove::null(),
         );
     }
 
+    let in_check = board.in_check();
+    let params = info.params;
+    // the static evaluation only decides about pruning at nodes that are not on the
+    // principal variation and not in check
+    let static_eval = if pv_node || in_check || singular_search {
+        None
+    } else {
+        Some(color as Score * (info.evaluator)(&board))
+    };
+    let depth_i = depth as i32;
+    let single_reply = moves.len() == 1 && !singular_search;
+
+    if let Some(eval) = static_eval {
+        // reverse futility pruning: far enough above beta that no move will drop below it
+        if depth_i <= params.reverse_futility_depth
+            && !score::is_mate(beta)
+            && eval - params.reverse_futility_margin * depth_i >= beta
+        {
+            return (eval, BitMove::null());
+        }
+        // razoring: so far below alpha that only captures can help
+        if depth_i <= params.razor_depth
+            && !score::is_mate(alpha)
+            && eval + params.razor_margin * depth_i < alpha
+        {
+            let score = quiesce(board.shallow_clone(), color, alpha, beta, false, info, ply);
+            if info.stopped() {
+                return (0, BitMove::null());
+            }
+            if score < alpha {
+                return (score, BitMove::null());
+            }
+        }
+    }
+    // futility pruning: quiet moves cannot bring the evaluation up to alpha
+    let futile = match static_eval {
+        Some(eval) => {
+            depth_i <= params.futility_depth
+                && !score::is_mate(alpha)
+                && eval + params.futility_margin * depth_i <= alpha
+        }
+        None => false,
+    };
+
     //null move pruning
     //The apply_null_move and undo_null_move are unsafe operations
     unsafe {
@@ -97,51 +513,169 @@ pub fn nega_max(
             _ => panic!("In valid color"),
         };
         if do_null
-            && !board.in_check()
+            && !in_check
             && board.ply() > 0
             && board.non_pawn_material(curr_pl) > 0
             && depth > 3
         {
             board.apply_null_move();
+            info.history.push(0);
+            info.extensions[ply + 1] = info.extensions[ply];
             let (mut score, _) = nega_max(
                 board.shallow_clone(),
-                depth - 1 - R,
+                depth.saturating_sub(1 + params.null_move_reduction as u8),
                 -color,
                 -beta,
-                -beta + 1.0,
-                transition_table,
-                false,
-                evaluator,
+                -beta + 1,
+                info,
+                ply + 1,
                 false,
             );
             score = -score;
+            info.history.pop();
             board.undo_null_move();
+            if info.stopped() {
+                return (0, BitMove::null());
+            }
 
-            if score > beta {
+            if score >= beta {
                 return (beta, BitMove::null());
             }
         }
     }
 
-    let mut best_score: f32 = -9999.0;
+    // singular extension: the table move is far better than all the others, so the line
+    // depends on it and it is searched deeper
+    let mut singular_move = BitMove::null();
+    if let Some(entry) = tt_entry {
+        if !root
+            && !singular_search
+            && depth_i >= params.singular_min_depth
+            && entry.move_type != MoveType::UpperBound
+            && entry.depth as i32 >= depth_i - 3
+            && !score::is_mate(entry.value)
+            && moves.contains(&entry.mv)
+        {
+            let singular_beta = score::from_tt(entry.value, ply) - params.singular_margin * depth_i;
+            info.excluded[ply] = entry.mv;
+            let (score, _) = nega_max(
+                board.shallow_clone(),
+                depth / 2,
+                color,
+                singular_beta - 1,
+                singular_beta,
+                info,
+                ply,
+                false,
+            );
+            info.excluded[ply] = BitMove::null();
+            if info.stopped() {
+                return (0, BitMove::null());
+            }
+            if score < singular_beta {
+                singular_move = entry.mv;
+            }
+        }
+    }
+
+    let mut best_score = -INFINITE;
     let mut best_move: BitMove = BitMove::null();
+    let mut picker = MovePicker::new(
+        moves,
+        tt_move,
+        info.heuristics.killers(ply),
+        info.heuristics.counter(board.last_move()),
+    );
+    // quiet moves searched so far, scored down in the history when a later one cuts off
+    let mut quiets_tried: Vec<BitMove> = Vec::new();
+    let mut moves_searched: i32 = 0;
+    let late_move_limit = params.lmp_base + depth_i * depth_i;
+
+    while let Some(mv) = picker.next(&board, &info.heuristics) {
+        let quiet = !mv.is_capture() && !mv.is_promo();
+        // once a move has been searched, quiet moves that are hopeless or come too late
+        // are skipped, unless they give check
+        if quiet
+            && moves_searched > 0
+            && !pv_node
+            && !in_check
+            && (futile
+                || (depth_i <= params.lmp_depth && quiets_tried.len() as i32 >= late_move_limit))
+            && !board.gives_check(mv)
+        {
+            continue;
+        }
 
-    for mv in moves {
         board.apply_move(mv);
-        let (mut score, _) = nega_max(
-            board.shallow_clone(),
-            depth - 1,
-            -color,
-            -beta,
-            -alpha,
-            transition_table,
-            false,
-            evaluator,
-            true,
-        );
-        score = -score;
+        info.history.push(hash);
+        // forcing moves are searched a ply deeper, up to a limit for the whole line
+        let extend = info.extensions[ply] < params.max_extensions
+            && (board.in_check() || single_reply || mv == singular_move);
+        let extension = extend as i32;
+        info.extensions[ply + 1] = info.extensions[ply] + extension;
+        let new_depth = depth - 1 + extension as u8;
+        // principal variation search: only the first move gets the full window, the others
+        // just have to be proven worse and are searched again if that fails
+        let mut score = 0;
+        if moves_searched > 0 {
+            // late move reductions: late quiet moves are searched less deep first
+            let mut reduction = 0;
+            if quiet
+                && depth_i >= params.lmr_min_depth
+                && moves_searched >= params.lmr_min_moves
+                && !in_check
+                && !board.in_check()
+            {
+                reduction = params.reduction(depth, moves_searched);
+                if pv_node {
+                    reduction -= 1;
+                }
+                reduction = reduction.min(depth_i - 2).max(0);
+            }
+            score = -nega_max(
+                board.shallow_clone(),
+                new_depth - reduction as u8,
+                -color,
+                -alpha - 1,
+                -alpha,
+                info,
+                ply + 1,
+                true,
+            )
+            .0;
+            if reduction > 0 && score > alpha {
+                score = -nega_max(
+                    board.shallow_clone(),
+                    new_depth,
+                    -color,
+                    -alpha - 1,
+                    -alpha,
+                    info,
+                    ply + 1,
+                    true,
+                )
+                .0;
+            }
+        }
+        if moves_searched == 0 || (score > alpha && score < beta) {
+            score = -nega_max(
+                board.shallow_clone(),
+                new_depth,
+                -color,
+                -beta,
+                -alpha,
+                info,
+                ply + 1,
+                true,
+            )
+            .0;
+        }
+        info.history.pop();
 
         board.undo_move();
+        if info.stopped() {
+            return (0, BitMove::null());
+        }
         if score > best_score {
             best_score = score;
             best_move = mv;
@@ -149,11 +683,20 @@ pub fn nega_max(
 
         if score > alpha {
             alpha = score;
+            info.update_pv(ply, mv);
         }
 
-        if alpha > beta {
+        if alpha >= beta {
+            if quiet {
+                info.heuristics
+                    .update(&board, mv, &quiets_tried, ply, depth);
+            }
             break;
         }
+        if quiet {
+            quiets_tried.push(mv);
+        }
+        moves_searched += 1;
     }
 
     let tp: MoveType;
@@ -165,43 +708,275 @@ pub fn nega_max(
         tp = MoveType::Exact;
     }
 
-    let new_entry = TransitionEntry {
-        value: best_score,
-        mv: best_move,
-        depth: depth,
-        move_type: tp,
+    if !singular_search {
+        info.transition_table
+            .store(hash, score::to_tt(best_score, ply), best_move, depth, tp);
+    }
+    return (best_score, best_move);
+}
+
+/// Searches the position with increasing depth until the depth or time limit is reached,
+/// or until `stop` is set. The transposition table is kept between iterations so every
+/// iteration benefits from the previous ones. `report` is called after each completed
+/// iteration and the result of the last completed iteration is returned.
+///
+/// With more than one thread in `params` this is a Lazy SMP search: helper threads search
+/// the same position through the shared table, starting at different depths so they get
+/// ahead of the main thread and fill the table with results it will need. Only the main
+/// thread reports and decides when to stop.
+pub fn iterative_deepening<F: FnMut(&SearchResult)>(
+    board: &Board,
+    limits: &SearchLimits,
+    transition_table: &mut TranspositionTable,
+    evaluator: fn(&Board) -> Score,
+    params: &SearchParams,
+    stop: Arc<AtomicBool>,
+    mut report: F,
+) -> SearchResult {
+    let start = Instant::now();
+    let time = TimeManager::new(limits, board.turn());
+    transition_table.new_search();
+    let transition_table: &TranspositionTable = transition_table;
+    let helpers_stop = Arc::new(AtomicBool::new(false));
+    let helper_nodes = Arc::new(AtomicU64::new(0));
+
+    return thread::scope(|scope| {
+        let helpers: Vec<_> = (1..params.threads.clamp(1, MAX_THREADS))
+            .map(|id| {
+                let board = board.parallel_clone();
+                let mut info = thread_info(
+                    &board,
+                    transition_table,
+                    evaluator,
+                    params,
+                    helpers_stop.clone(),
+                );
+                info.shared_nodes = Some(helper_nodes.clone());
+                scope.spawn(move || helper_search(&board, id, &mut info))
+            })
+            .collect();
+
+        let mut info = thread_info(board, transition_table, evaluator, params, stop);
+        info.deadline = time.maximum().map(|maximum| start + maximum);
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        let color = info.root_color;
+
+        let mut result = SearchResult {
+            score: 0,
+            best_move: BitMove::null(),
+            pv: Vec::new(),
+            depth: 0,
+            nodes: 0,
+            hashfull: 0,
+            elapsed: Duration::from_secs(0),
+        };
+
+        for depth in 1..=max_depth {
+            let (score, mv) = aspiration_search(board, depth, color, result.score, &mut info);
+            if info.stopped() {
+                break;
+            }
+            let mut pv = info.principal_variation();
+            if pv.first() != Some(&mv) {
+                pv = vec![mv];
+            }
+            result = SearchResult {
+                score,
+                best_move: mv,
+                pv,
+                depth,
+                nodes: info.nodes + helper_nodes.load(Ordering::Relaxed),
+                hashfull: info.transition_table.hashfull(),
+                elapsed: start.elapsed(),
+            };
+            report(&result);
+
+            if mv.is_null() || time.iteration_done(result.elapsed) {
+                break;
+            }
+        }
+
+        helpers_stop.store(true, Ordering::Relaxed);
+        result.nodes = info.nodes;
+        for helper in helpers {
+            result.nodes += helper.join().unwrap();
+        }
+
+        // Stopped before the first iteration completed, any legal move beats no move.
+        if result.best_move.is_null() {
+            if let Some(mv) = board.generate_moves().iter().next() {
+                result.best_move = *mv;
+                result.pv = vec![*mv];
+            }
+        }
+        result.elapsed = start.elapsed();
+        return result;
+    });
+}
+
+/// The state of one search thread at the root of `board`.
+fn thread_info<'a>(
+    board: &Board,
+    transition_table: &'a TranspositionTable,
+    evaluator: fn(&Board) -> Score,
+    params: &SearchParams,
+    stop: Arc<AtomicBool>,
+) -> SearchInfo<'a> {
+    let mut info = SearchInfo::new(transition_table, evaluator);
+    info.stop = stop;
+    info.params = *params;
+    info.root_color = match board.turn() {
+        Player::White => 1,
+        Player::Black => -1,
     };
+    info.set_game_history(game_history(board));
+    return info;
+}
 
-    transition_table.insert(hash, new_entry);
-    return (best_score, best_move);
+/// Iterative deepening of a helper thread until it is stopped. Every other helper starts
+/// a ply deeper so they do not all search the same depth. Returns the nodes searched.
+fn helper_search(board: &Board, id: usize, info: &mut SearchInfo) -> u64 {
+    let color = info.root_color;
+    let mut previous = 0;
+    let mut depth = 1 + (id % 2) as u8;
+    while depth <= MAX_DEPTH {
+        let (score, mv) = aspiration_search(board, depth, color, previous, info);
+        if info.stopped() || mv.is_null() {
+            break;
+        }
+        previous = score;
+        depth += 1;
+    }
+    return info.nodes;
+}
+
+/// Searches the root with a narrow window around the score of the previous iteration,
+/// widening it on the side the score fell outside of until the score lies within.
+fn aspiration_search(
+    board: &Board,
+    depth: u8,
+    color: i8,
+    previous: Score,
+    info: &mut SearchInfo,
+) -> (Score, BitMove) {
+    let mut delta = ASPIRATION_WINDOW;
+    let (mut alpha, mut beta) = (-INFINITE, INFINITE);
+    if depth >= ASPIRATION_MIN_DEPTH && !score::is_mate(previous) {
+        alpha = (previous - delta).max(-INFINITE);
+        beta = (previous + delta).min(INFINITE);
+    }
+    loop {
+        let (score, mv) = nega_max(
+            board.shallow_clone(),
+            depth,
+            color,
+            alpha,
+            beta,
+            info,
+            0,
+            true,
+        );
+        if info.stopped() {
+            return (score, mv);
+        }
+        delta *= 2;
+        if score <= alpha && alpha > -INFINITE {
+            alpha = (alpha - delta).max(-INFINITE);
+        } else if score >= beta && beta < INFINITE {
+            beta = (beta + delta).min(INFINITE);
+        } else {
+            return (score, mv);
+        }
+    }
 }
 
+/// Searches captures until the position is quiet, so the evaluation is not taken in the
+/// middle of an exchange. In check all evasions are searched instead. Queen promotions
+/// always count as captures, quiet moves giving check only when `checks` is set, which
+/// the search only does at the first ply of quiescence.
 fn quiesce(
     mut board: Board,
     color: i8,
-    mut alpha: f32,
-    beta: f32,
-    depth: u8,
-    evaluator: fn(&Board) -> f32,
-) -> f32 {
-    let standpat = (color as f32) * evaluator(&board);
-    if depth == 0 {
-        return standpat;
+    mut alpha: Score,
+    beta: Score,
+    checks: bool,
+    info: &mut SearchInfo,
+    ply: usize,
+) -> Score {
+    if info.visit_node() {
+        return 0;
+    }
+    let moves = board.generate_moves().vec();
+    let in_check = board.in_check();
+    if moves.is_empty() {
+        return if in_check {
+            score::mated_in(ply)
+        } else {
+            info.draw_score(color)
+        };
     }
-    if standpat >= beta {
-        return beta;
+    if ply >= MAX_PLY {
+        return color as Score * (info.evaluator)(&board);
     }
 
-    if alpha < standpat {
-        alpha = standpat;
+    let hash = tt::position_key(&board);
+    let pv_node = beta - alpha > 1;
+    let mut tt_move = BitMove::null();
+    if let Some(entry) = info.transition_table.probe(hash) {
+        tt_move = entry.mv;
+        let value = score::from_tt(entry.value, ply);
+        let cutoff = match entry.move_type {
+            MoveType::Exact => true,
+            MoveType::LowerBound => value >= beta,
+            MoveType::UpperBound => value <= alpha,
+        };
+        if !pv_node && cutoff {
+            return value;
+        }
     }
 
-    let moves = board.generate_moves();
-    for mv in moves {
-        if !board.is_capture(mv)
-            || standpat + eval::piece_values(board.piece_last_captured()) + 200.0 < alpha
-        {
-            continue;
+    let alpha_original = alpha;
+    let mut best_score = -INFINITE;
+    let mut standpat = -INFINITE;
+    // in check there is no standing pat, every evasion has to be tried
+    if !in_check {
+        standpat = color as Score * (info.evaluator)(&board);
+        if standpat >= beta {
+            return standpat;
+        }
+        alpha = alpha.max(standpat);
+        best_score = standpat;
+    }
+
+    let candidates: Vec<BitMove> = if in_check {
+        moves
+    } else {
+        moves
+            .into_iter()
+            .filter(|mv| {
+                let tactical = match mv.is_promo() {
+                    true => mv.promo_piece() == PieceType::Q,
+                    false => mv.is_capture(),
+                };
+                tactical || (checks && board.gives_check(*mv))
+            })
+            .collect()
+    };
+    let mut picker = MovePicker::new(candidates, tt_move, [BitMove::null(); 2], BitMove::null());
+    let mut best_move = BitMove::null();
+
+    while let Some(mv) = picker.next(&board, &info.heuristics) {
+        if !in_check && !mv.is_promo() {
+            // delta pruning: even winning the captured piece for free does not raise alpha
+            if mv.is_capture()
+                && standpat + eval::piece_values(board.captured_piece(mv)) + 200 < alpha
+            {
+                continue;
+            }
+            // moves that lose material in the exchange on the target square
+            if !see::see(&board, mv, 0) {
+                continue;
+            }
         }
 
         board.apply_move(mv);
@@ -210,44 +985,69 @@ fn quiesce(
             -color,
             -beta,
             -alpha,
-            depth - 1,
-            evaluator,
+            false,
+            info,
+            ply + 1,
         );
         board.undo_move();
-        if score >= beta {
-            return beta;
+        if info.stopped() {
+            return 0;
+        }
+        if score > best_score {
+            best_score = score;
+            best_move = mv;
         }
-
         if score > alpha {
             alpha = score;
         }
+        if alpha >= beta {
+            break;
+        }
     }
-    return alpha;
+
+    let move_type = if best_score >= beta {
+        MoveType::LowerBound
+    } else if best_score > alpha_original {
+        MoveType::Exact
+    } else {
+        MoveType::UpperBound
+    };
+    info.transition_table
+        .store(hash, score::to_tt(best_score, ply), best_move, 0, move_type);
+    return best_score;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::utils;
-    use pleco::{Board, Player};
+    use pleco::Board;
+
+    /// Searches `board` with a fresh table and a stop flag that is never set.
+    fn search(board: &Board, limits: &SearchLimits, params: &SearchParams) -> SearchResult {
+        return search_reporting(board, limits, params, |_| {});
+    }
+
+    /// Like `search`, calling `report` after every completed iteration.
+    fn search_reporting<F: FnMut(&SearchResult)>(
+        board: &Board,
+        limits: &SearchLimits,
+        params: &SearchParams,
+        report: F,
+    ) -> SearchResult {
+        let mut tt = TranspositionTable::new(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        return iterative_deepening(board, limits, &mut tt, eval::eval, params, stop, report);
+    }
 
     #[test]
     fn take_queen_white() {
         let fen = "rnb1kbnr/pppp1ppp/8/4p1q1/4P1Q1/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3";
         let board = Board::from_fen(fen).unwrap();
-        let mut tt: HashMap<u64, TransitionEntry> = HashMap::new();
+        let tt = TranspositionTable::new(1);
+        let mut info = SearchInfo::new(&tt, eval::eval);
 
-        let (_, mv) = nega_max(
-            board,
-            4,
-            1,
-            -9999.0,
-            9999.0,
-            &mut tt,
-            true,
-            eval::eval,
-            true,
-        );
+        let (_, mv) = nega_max(board, 4, 1, -INFINITE, INFINITE, &mut info, 0, true);
         assert_eq!(
             mv.stringify(),
             "g4g5",
@@ -262,37 +1062,28 @@ mod tests {
         let board =
             Board::from_fen("rnb1kbnr/pppp1ppp/8/4p1q1/4P1Q1/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3")
                 .unwrap();
-        let mut tt: HashMap<u64, TransitionEntry> = HashMap::new();
-        let (score, mv) = nega_max(
-            board,
-            4,
-            -1,
-            -9999.0,
-            9999.0,
-            &mut tt,
-            true,
-            eval::eval,
-            true,
-        );
+        let tt = TranspositionTable::new(1);
+        let mut info = SearchInfo::new(&tt, eval::eval);
+        let (score, mv) = nega_max(board, 4, -1, -INFINITE, INFINITE, &mut info, 0, true);
         assert_ne!(mv.stringify(), "g4g5");
-        assert_ne!(score, 0.0);
+        assert_ne!(score, 0);
     }
 
     #[test]
     fn mate_in_one_1() {
         let mut board = Board::from_fen("k7/5R2/6R1/8/8/8/4K3/8 w - - 0 1").unwrap();
-        let mut tt: HashMap<u64, TransitionEntry> = HashMap::new();
+        let tt = TranspositionTable::new(1);
+        let mut info = SearchInfo::new(&tt, eval::eval);
 
         for depth in 1..4 {
             let (_, mv) = nega_max(
                 board.shallow_clone(),
                 depth,
                 1,
-                -9999.0,
-                9999.0,
-                &mut tt,
-                true,
-                eval::eval,
+                -INFINITE,
+                INFINITE,
+                &mut info,
+                0,
                 true,
             );
             board.apply_move(mv);
@@ -310,7 +1101,8 @@ mod tests {
     fn mate_in_one_2() {
         let fen = "1k6/8/8/8/8/3n4/6PR/6RK b Q - 0 1";
         let mut board = Board::from_fen(fen).unwrap();
-        let mut tt: HashMap<u64, TransitionEntry> = HashMap::new();
+        let tt = TranspositionTable::new(1);
+        let mut info = SearchInfo::new(&tt, eval::eval);
 
         let color = -1;
         for depth in 1..4 {
@@ -318,11 +1110,10 @@ mod tests {
                 board.shallow_clone(),
                 depth,
                 color,
-                -9999.0,
-                9999.0,
-                &mut tt,
-                true,
-                eval::eval,
+                -INFINITE,
+                INFINITE,
+                &mut info,
+                0,
                 true,
             );
             board.apply_move(mv);
@@ -360,4 +1151,300 @@ mod tests {
         let board = utils::play_x_moves(board, 3, 4, color, eval::eval);
         assert!(board.checkmate());
     }
+
+    #[test]
+    fn quiescence() {
+        let tt = TranspositionTable::new(1);
+        let mut info = SearchInfo::new(&tt, eval::eval);
+
+        // the pawn promotes to a queen
+        let board = Board::from_fen("8/P6k/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let standpat = eval::eval(&board);
+        let score = quiesce(
+            board.shallow_clone(),
+            1,
+            -INFINITE,
+            INFINITE,
+            false,
+            &mut info,
+            0,
+        );
+        assert!(score > standpat + 500, "{} {}", score, standpat);
+        let entry = info.transition_table.probe(tt::position_key(&board));
+        assert_eq!(entry.unwrap().mv.stringify(), "a7a8q");
+
+        // in check there is no standing pat, the knight forks king and queen
+        let board = Board::from_fen("k7/8/8/8/8/2Q5/4n3/6K1 w - - 0 1").unwrap();
+        assert!(eval::eval(&board) > 0);
+        let score = quiesce(board, 1, -INFINITE, INFINITE, false, &mut info, 0);
+        assert!(score < 0, "{}", score);
+
+        // a quiet check at the first ply attacks the rook as well
+        let board = Board::from_fen("6k1/8/8/8/8/1r6/8/2Q3K1 w - - 0 1").unwrap();
+        let standpat = eval::eval(&board);
+        let score = quiesce(
+            board.shallow_clone(),
+            1,
+            -INFINITE,
+            INFINITE,
+            true,
+            &mut info,
+            0,
+        );
+        assert!(score > standpat + 300, "{} {}", score, standpat);
+    }
+
+    #[test]
+    fn lazy_smp() {
+        let fen = "k7/4R3/8/8/8/4R3/8/3K4 w - - 0 1";
+        let board = Board::from_fen(fen).unwrap();
+        let params = SearchParams {
+            threads: 4,
+            ..Default::default()
+        };
+        let mut reported = Vec::new();
+        let result = search_reporting(&board, &SearchLimits::depth(6), &params, |info| {
+            reported.push(info.nodes)
+        });
+        assert_eq!(result.depth, 6);
+        assert_eq!(score::mate_moves(result.score), Some(2));
+        assert!(board.generate_moves().contains(&result.best_move));
+        assert!(result.nodes >= *reported.last().unwrap());
+
+        // the helpers search next to the main thread and their nodes are counted too, so
+        // the total is above that of a single thread searching to the same depth alone
+        let board = Board::start_pos();
+        let search_with = |threads: usize| {
+            let params = SearchParams {
+                threads,
+                ..Default::default()
+            };
+            return search(&board, &SearchLimits::depth(6), &params);
+        };
+        let single = search_with(1);
+        let parallel = search_with(4);
+        assert_eq!(parallel.depth, 6);
+        assert!(
+            parallel.nodes > single.nodes,
+            "{} {}",
+            parallel.nodes,
+            single.nodes
+        );
+    }
+
+    #[test]
+    fn check_extensions() {
+        // smothered mate, the forced recapture and the quiet knight move only fit into two
+        // plies when the checks are extended. Razoring is off, it would leave the quiet mate
+        // to quiescence.
+        let fen = "r6k/6pp/8/6N1/8/1Q6/B7/6K1 w - - 0 1";
+        let board = Board::from_fen(fen).unwrap();
+        let params = SearchParams {
+            razor_depth: 0,
+            ..Default::default()
+        };
+        let result = search(&board, &SearchLimits::depth(2), &params);
+        assert_eq!(score::mate_moves(result.score), Some(2));
+        assert_eq!(result.best_move.stringify(), "b3g8");
+
+        let params = SearchParams {
+            max_extensions: 0,
+            ..params
+        };
+        let result = search(&board, &SearchLimits::depth(2), &params);
+        assert_eq!(score::mate_moves(result.score), None);
+    }
+
+    #[test]
+    fn iterative_deepening_depth() {
+        let mut board = Board::from_fen("k7/5R2/6R1/8/8/8/4K3/8 w - - 0 1").unwrap();
+        let mut depths = Vec::new();
+        let limits = SearchLimits::depth(3);
+        let result = search_reporting(&board, &limits, &SearchParams::default(), |info| {
+            depths.push(info.depth)
+        });
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(result.depth, 3);
+        assert_eq!(score::mate_moves(result.score), Some(1));
+        board.apply_move(result.best_move);
+        assert!(board.checkmate());
+    }
+
+    #[test]
+    fn principal_variation() {
+        let fen = "k7/4R3/8/8/8/4R3/8/3K4 w - - 0 1";
+        let mut board = Board::from_fen(fen).unwrap();
+        let result = search(&board, &SearchLimits::depth(4), &SearchParams::default());
+
+        assert_eq!(result.pv[0], result.best_move);
+        assert!(result.pv.len() >= 3, "pv too short: {:?}", result.pv);
+        for mv in &result.pv {
+            assert!(board.generate_moves().contains(mv));
+            board.apply_move(*mv);
+        }
+        assert!(board.checkmate());
+    }
+
+    #[test]
+    fn mate_distance_from_root() {
+        // deep enough that pruning near the leaves does not hide the quiet first move
+        let search = |board: &Board, tt: &mut TranspositionTable| {
+            return iterative_deepening(
+                board,
+                &SearchLimits::depth(5),
+                tt,
+                eval::eval,
+                &SearchParams::default(),
+                Arc::new(AtomicBool::new(false)),
+                |_| {},
+            );
+        };
+        // played into from the start of a game, the mate is still counted from the root
+        let mut board = Board::from_fen("k7/4R3/8/8/8/4R3/8/3K4 w - - 0 1").unwrap();
+        board.apply_uci_move("d1d2");
+        board.apply_uci_move("a8b8");
+        let mut tt = TranspositionTable::new(1);
+        let result = search(&board, &mut tt);
+        assert_eq!(score::mate_moves(result.score), Some(2));
+        assert_eq!(result.score, score::mate_in(3));
+
+        // mates stored in the table by the first search are one move shorter now
+        board.apply_move(result.pv[0]);
+        board.apply_move(result.pv[1]);
+        let result = search(&board, &mut tt);
+        assert_eq!(score::mate_moves(result.score), Some(1));
+        assert_eq!(result.score, score::mate_in(1));
+    }
+
+    #[test]
+    fn iterative_deepening_movetime() {
+        let board = Board::start_pos();
+        let limits = SearchLimits {
+            movetime: Some(200),
+            ..SearchLimits::default()
+        };
+        let start = Instant::now();
+        let result = search(&board, &limits, &SearchParams::default());
+        assert!(start.elapsed() < Duration::from_millis(1000));
+        assert!(result.depth >= 1);
+        assert!(board.generate_moves().contains(&result.best_move));
+    }
+
+    #[test]
+    fn insufficient_material_draws() {
+        let knight = Board::from_fen("k7/8/8/8/8/8/8/KN6 w - - 0 1").unwrap();
+        assert!(insufficient_material(&knight));
+        let bishops = Board::from_fen("k1b5/8/8/8/8/8/8/KB6 w - - 0 1").unwrap();
+        assert!(!insufficient_material(&bishops));
+        let rook = Board::from_fen("k7/8/8/8/8/8/8/KR6 w - - 0 1").unwrap();
+        assert!(!insufficient_material(&rook));
+    }
+
+    #[test]
+    fn fifty_move_rule() {
+        let tt = TranspositionTable::new(1);
+        let info = SearchInfo::new(&tt, eval::eval);
+        let board = Board::from_fen("k7/8/8/8/8/8/8/KR6 w - - 100 80").unwrap();
+        assert!(is_draw(&board, tt::position_key(&board), &info));
+        let board = Board::from_fen("k7/8/8/8/8/8/8/KR6 w - - 99 80").unwrap();
+        assert!(!is_draw(&board, tt::position_key(&board), &info));
+    }
+
+    #[test]
+    fn threefold_repetition() {
+        let mut board = Board::start_pos();
+        let tt = TranspositionTable::new(1);
+        let mut info = SearchInfo::new(&tt, eval::eval);
+        for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            board.apply_uci_move(mv);
+        }
+        info.set_game_history(game_history(&board));
+        assert!(!info.is_repetition(&board, tt::position_key(&board)));
+
+        for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            board.apply_uci_move(mv);
+        }
+        info.set_game_history(game_history(&board));
+        assert!(info.is_repetition(&board, tt::position_key(&board)));
+
+        // a capture resets the fifty move counter, nothing before it can repeat
+        board.apply_uci_move("e2e4");
+        board.apply_uci_move("d7d5");
+        board.apply_uci_move("e4d5");
+        assert!(game_history(&board).is_empty());
+    }
+
+    #[test]
+    fn tunable_params() {
+        let mut params = SearchParams::default();
+        for (name, min, max) in TUNABLE_PARAMS.iter() {
+            let value = params.tunable(name).unwrap();
+            assert!(*min <= value && value <= *max, "{}", name);
+        }
+        params.set_tunable("lmrbase", 120);
+        assert_eq!(params.lmr_base, 120);
+        params.set_tunable("NullMoveReduction", 100);
+        assert_eq!(params.null_move_reduction, 6);
+        params.set_tunable("Contempt", 100);
+        assert_eq!(params.contempt, 0);
+        assert_eq!(params.tunable("Unknown"), None);
+    }
+
+    #[test]
+    fn contempt() {
+        let board = Board::from_fen("k7/8/8/8/8/8/8/KN6 w - - 0 1").unwrap();
+        for contempt in [0, 50] {
+            let params = SearchParams {
+                contempt,
+                ..Default::default()
+            };
+            let result = search(&board, &SearchLimits::depth(3), &params);
+            assert_eq!(result.score, -contempt);
+        }
+    }
+
+    #[test]
+    fn stop_before_search() {
+        let board = Board::start_pos();
+        let mut tt = TranspositionTable::new(1);
+        let stop = Arc::new(AtomicBool::new(true));
+
+        let result = iterative_deepening(
+            &board,
+            &SearchLimits::depth(MAX_DEPTH),
+            &mut tt,
+            eval::eval,
+            &SearchParams::default(),
+            stop,
+            |_| panic!("no iteration should complete"),
+        );
+        assert_eq!(result.depth, 0);
+        assert!(board.generate_moves().contains(&result.best_move));
+    }
+
+    #[test]
+    fn stop_from_other_thread() {
+        let board = Board::start_pos();
+        let stop = Arc::new(AtomicBool::new(false));
+        let search_stop = stop.clone();
+
+        let handle = std::thread::spawn(move || {
+            let mut tt = TranspositionTable::new(1);
+            return iterative_deepening(
+                &board,
+                &SearchLimits::default(),
+                &mut tt,
+                eval::eval,
+                &SearchParams::default(),
+                search_stop,
+                |_| {},
+            );
+        });
+        std::thread::sleep(Duration::from_millis(100));
+        stop.store(true, Ordering::Relaxed);
+
+        let result = handle.join().unwrap();
+        assert!(result.depth < MAX_DEPTH);
+        assert!(!result.best_move.is_null());
+    }
 }
@@ -0,0 +1,158 @@
+use pleco::{BitBoard, BitMove, Board, PieceType, SQ};
+
+use crate::score::Score;
+
+/// Piece values used for exchanges. The king can never be captured, so it is worth more
+/// than everything else put together.
+fn see_value(piece: PieceType) -> Score {
+    return match piece {
+        PieceType::P => 100,
+        PieceType::N => 300,
+        PieceType::B => 300,
+        PieceType::R => 500,
+        PieceType::Q => 900,
+        PieceType::K => 20_000,
+        PieceType::None => 0,
+        PieceType::All => 0,
+    };
+}
+
+/// Static exchange evaluation: returns whether `mv` wins at least `threshold` centipawns
+/// once both sides have made all the captures on the target square that pay off for them,
+/// always recapturing with the least valuable piece. Pins are ignored.
+pub fn see(board: &Board, mv: BitMove, threshold: Score) -> bool {
+    if mv.is_castle() {
+        return threshold <= 0;
+    }
+    let from = mv.get_src();
+    let to = mv.get_dest();
+
+    // the value of the piece standing on the target square after the move
+    let mut on_square = see_value(board.moved_piece(mv).type_of());
+    let mut gain = see_value(board.captured_piece(mv));
+    if mv.is_promo() {
+        on_square = see_value(mv.promo_piece());
+        gain += on_square - see_value(PieceType::P);
+    }
+
+    // what the side to move is left with if the opponent does not recapture
+    let mut swap = gain - threshold;
+    if swap < 0 {
+        return false;
+    }
+    // and if it does, with the piece that just moved
+    swap = on_square - swap;
+    if swap <= 0 {
+        return true;
+    }
+
+    let mut occupied = board.occupied() ^ from.to_bb() ^ to.to_bb();
+    if mv.is_en_passant() {
+        // the captured pawn stands next to the target square, one rank closer to the mover
+        occupied ^= SQ(to.0 ^ 8).to_bb();
+    }
+    let mut player = board.turn();
+    // 1 if the side that made the move is ahead once the exchange stops
+    let mut result = 1;
+    loop {
+        player = !player;
+        let attackers = board.attackers_to(to, occupied) & occupied;
+        let own_attackers = attackers & board.get_occupied_player(player);
+        if own_attackers.is_empty() {
+            break;
+        }
+        result ^= 1;
+
+        let (piece, bb) = least_valuable(board, own_attackers);
+        if piece == PieceType::K {
+            // the king may only take if the opponent has nothing left to take back with
+            if (attackers & !board.get_occupied_player(player)).is_not_empty() {
+                result ^= 1;
+            }
+            break;
+        }
+        swap = see_value(piece) - swap;
+        if swap < result {
+            break;
+        }
+        occupied ^= bb;
+    }
+    return result == 1;
+}
+
+/// The least valuable piece among `attackers` and a bitboard of its square.
+fn least_valuable(board: &Board, attackers: BitBoard) -> (PieceType, BitBoard) {
+    for piece in [
+        PieceType::P,
+        PieceType::N,
+        PieceType::B,
+        PieceType::R,
+        PieceType::Q,
+    ] {
+        let bb = attackers & board.piece_bb_both_players(piece);
+        if bb.is_not_empty() {
+            return (piece, bb.lsb());
+        }
+    }
+    return (PieceType::K, attackers.lsb());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::find_move;
+
+    fn see_at(fen: &str, uci: &str, threshold: Score) -> bool {
+        let board = Board::from_fen(fen).unwrap();
+        return see(&board, find_move(&board, uci), threshold);
+    }
+
+    #[test]
+    fn undefended_piece() {
+        let fen = "4k3/8/8/3r4/8/8/8/3RK3 w - - 0 1";
+        assert!(see_at(fen, "d1d5", 500));
+        assert!(!see_at(fen, "d1d5", 501));
+    }
+
+    #[test]
+    fn defended_piece() {
+        // the knight is defended by a pawn, taking it with the queen loses the queen
+        let fen = "4k3/8/4p3/3n4/8/8/3Q4/4K3 w - - 0 1";
+        assert!(!see_at(fen, "d2d5", 0));
+        assert!(see_at(fen, "d2d5", -600));
+        // with a pawn it wins a knight for a pawn
+        let fen = "4k3/8/4p3/3n4/2P5/8/8/4K3 w - - 0 1";
+        assert!(see_at(fen, "c4d5", 200));
+        assert!(!see_at(fen, "c4d5", 201));
+    }
+
+    #[test]
+    fn x_rays() {
+        // the rooks behind the first ones join in once those have moved
+        let fen = "3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1";
+        assert!(!see_at(fen, "d2d5", 0));
+        assert!(see_at(fen, "d2d5", -400));
+        let fen = "4k3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1";
+        assert!(see_at(fen, "d2d5", 100));
+    }
+
+    #[test]
+    fn king_recaptures() {
+        // the king can take back because nothing defends the pawn
+        let fen = "8/8/8/4k3/3p4/8/8/3QK3 w - - 0 1";
+        assert!(!see_at(fen, "d1d4", 0));
+        // but not when the queen is defended
+        let fen = "8/8/8/4k3/3p4/8/3R4/3QK3 w - - 0 1";
+        assert!(see_at(fen, "d2d4", 100));
+    }
+
+    #[test]
+    fn quiet_moves_and_promotions() {
+        // moving the rook where a pawn takes it
+        let fen = "4k3/8/2p5/8/8/8/8/1R2K3 w - - 0 1";
+        assert!(!see_at(fen, "b1b5", 0));
+        assert!(see_at(fen, "b1b4", 0));
+        let fen = "4k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        assert!(see_at(fen, "a7a8q", 800));
+    }
+}
@@ -0,0 +1,185 @@
+use pleco::Player;
+use std::time::Duration;
+
+/// Time reserved per move for communication and move transfer, in milliseconds.
+const MOVE_OVERHEAD: u64 = 30;
+/// Number of moves we expect to still play when the GUI does not send `movestogo`.
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+/// The limits of a single search, mirroring the arguments of the UCI `go` command.
+/// All times are in milliseconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+    pub depth: Option<u8>,
+    /// Search until stopped, ignoring any clock limits.
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u8) -> SearchLimits {
+        return SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        };
+    }
+
+    /// Whether the search of `player` is limited by a move time or by its own clock. The
+    /// clock of the opponent alone does not limit it.
+    pub fn is_timed(&self, player: Player) -> bool {
+        let clock = match player {
+            Player::White => self.wtime,
+            Player::Black => self.btime,
+        };
+        return !self.infinite && (self.movetime.is_some() || clock.is_some());
+    }
+}
+
+/// Decides how much of the clock a search may use.
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    optimum: Option<Duration>,
+    maximum: Option<Duration>,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, player: Player) -> TimeManager {
+        if !limits.is_timed(player) {
+            return TimeManager::unlimited();
+        }
+
+        if let Some(movetime) = limits.movetime {
+            let budget = Duration::from_millis(movetime.saturating_sub(MOVE_OVERHEAD).max(1));
+            return TimeManager {
+                optimum: Some(budget),
+                maximum: Some(budget),
+            };
+        }
+
+        let (time, inc) = match player {
+            Player::White => (limits.wtime, limits.winc.unwrap_or(0)),
+            Player::Black => (limits.btime, limits.binc.unwrap_or(0)),
+        };
+        let time = match time {
+            Some(time) => time.saturating_sub(MOVE_OVERHEAD).max(1),
+            None => return TimeManager::unlimited(),
+        };
+
+        let moves_to_go = limits.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        // never more than half of the clock, even for the last move before the time
+        // control, so a slow reply does not lose on time
+        let share = time / moves_to_go + inc * 3 / 4;
+        let maximum = (share * 4).min(time / 2).max(1);
+        let optimum = share.min(maximum);
+        return TimeManager {
+            optimum: Some(Duration::from_millis(optimum)),
+            maximum: Some(Duration::from_millis(maximum)),
+        };
+    }
+
+    fn unlimited() -> TimeManager {
+        return TimeManager {
+            optimum: None,
+            maximum: None,
+        };
+    }
+
+    /// The time after which a running iteration is aborted.
+    pub fn maximum(&self) -> Option<Duration> {
+        return self.maximum;
+    }
+
+    /// Called after an iteration has completed. The next iteration usually takes several
+    /// times longer than all previous ones together, so we only start it when less than
+    /// half of the optimum time has been used.
+    pub fn iteration_done(&self, elapsed: Duration) -> bool {
+        return match self.optimum {
+            Some(optimum) => elapsed * 2 >= optimum,
+            None => false,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_limits() {
+        let tm = TimeManager::new(&SearchLimits::depth(5), Player::White);
+        assert_eq!(tm.optimum, None);
+        assert_eq!(tm.maximum, None);
+        assert!(!tm.iteration_done(Duration::from_secs(1000)));
+    }
+
+    #[test]
+    fn movetime() {
+        let limits = SearchLimits {
+            movetime: Some(1000),
+            ..SearchLimits::default()
+        };
+        let tm = TimeManager::new(&limits, Player::Black);
+        assert_eq!(tm.optimum, Some(Duration::from_millis(970)));
+        assert_eq!(tm.maximum, Some(Duration::from_millis(970)));
+    }
+
+    #[test]
+    fn clock_uses_own_time() {
+        let limits = SearchLimits {
+            wtime: Some(60_030),
+            btime: Some(1_030),
+            winc: Some(1000),
+            ..SearchLimits::default()
+        };
+        let white = TimeManager::new(&limits, Player::White);
+        assert_eq!(white.optimum, Some(Duration::from_millis(2750)));
+        assert_eq!(white.maximum, Some(Duration::from_millis(11_000)));
+
+        let black = TimeManager::new(&limits, Player::Black);
+        assert!(black.optimum.unwrap() < Duration::from_millis(100));
+        assert!(black.maximum.unwrap() <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn opponent_clock_only() {
+        let limits = SearchLimits {
+            wtime: Some(1000),
+            ..SearchLimits::default()
+        };
+        assert!(limits.is_timed(Player::White));
+        assert!(!limits.is_timed(Player::Black));
+        let tm = TimeManager::new(&limits, Player::Black);
+        assert_eq!(tm.maximum, None);
+    }
+
+    #[test]
+    fn moves_to_go() {
+        let limits = SearchLimits {
+            btime: Some(10_030),
+            movestogo: Some(1),
+            ..SearchLimits::default()
+        };
+        let tm = TimeManager::new(&limits, Player::Black);
+        assert_eq!(tm.optimum, Some(Duration::from_millis(5_000)));
+        assert_eq!(tm.maximum, Some(Duration::from_millis(5_000)));
+        assert!(tm.iteration_done(Duration::from_millis(2_500)));
+        assert!(!tm.iteration_done(Duration::from_millis(2_000)));
+    }
+
+    #[test]
+    fn infinite_ignores_clock() {
+        let limits = SearchLimits {
+            wtime: Some(1000),
+            infinite: true,
+            ..SearchLimits::default()
+        };
+        let tm = TimeManager::new(&limits, Player::White);
+        assert_eq!(tm.optimum, None);
+        assert_eq!(tm.maximum, None);
+    }
+}
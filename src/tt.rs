@@ -0,0 +1,413 @@
+use pleco::{BitMove, Board, Player};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::score::Score;
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+pub const MAX_SIZE_MB: usize = 65536;
+const ENTRIES_PER_BUCKET: usize = 4;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MoveType {
+    Exact,
+    UpperBound,
+    LowerBound,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TransitionEntry {
+    pub key: u64,
+    pub value: Score,
+    pub mv: BitMove,
+    pub depth: u8,
+    pub move_type: MoveType,
+    age: u8,
+}
+
+impl TransitionEntry {
+    fn empty() -> TransitionEntry {
+        return TransitionEntry {
+            key: 0,
+            value: 0,
+            mv: BitMove::null(),
+            depth: 0,
+            move_type: MoveType::UpperBound,
+            age: 0,
+        };
+    }
+
+    fn is_empty(&self) -> bool {
+        return self.key == 0;
+    }
+
+    /// Packs everything but the key into 64 bits.
+    fn data(&self) -> u64 {
+        let move_type = match self.move_type {
+            MoveType::Exact => 0,
+            MoveType::UpperBound => 1,
+            MoveType::LowerBound => 2,
+        };
+        return (self.value as i16 as u16 as u64)
+            | (self.mv.get_raw() as u64) << 16
+            | (self.depth as u64) << 32
+            | move_type << 40
+            | (self.age as u64) << 48;
+    }
+
+    fn from_data(key: u64, data: u64) -> TransitionEntry {
+        let move_type = match (data >> 40) & 0xff {
+            0 => MoveType::Exact,
+            1 => MoveType::UpperBound,
+            _ => MoveType::LowerBound,
+        };
+        return TransitionEntry {
+            key,
+            value: data as u16 as i16 as Score,
+            mv: BitMove::new((data >> 16) as u16),
+            depth: (data >> 32) as u8,
+            move_type,
+            age: (data >> 48) as u8,
+        };
+    }
+}
+
+/// An entry shared between search threads. The key is stored xor-ed with the data, so
+/// an entry torn by two threads writing at the same time no longer matches its key and
+/// is never returned, without any locking.
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    fn load(&self) -> TransitionEntry {
+        let data = self.data.load(Ordering::Relaxed);
+        let key = self.key.load(Ordering::Relaxed) ^ data;
+        if key == 0 {
+            return TransitionEntry::empty();
+        }
+        return TransitionEntry::from_data(key, data);
+    }
+
+    fn save(&self, entry: &TransitionEntry) {
+        let data = entry.data();
+        self.key.store(entry.key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.key.store(0, Ordering::Relaxed);
+        self.data.store(0, Ordering::Relaxed);
+    }
+}
+
+struct Bucket {
+    entries: [Slot; ENTRIES_PER_BUCKET],
+}
+
+impl Bucket {
+    fn new() -> Bucket {
+        return Bucket {
+            entries: std::array::from_fn(|_| Slot {
+                key: AtomicU64::new(0),
+                data: AtomicU64::new(0),
+            }),
+        };
+    }
+}
+
+/// A fixed-size transposition table. Positions are mapped to buckets of a few entries,
+/// when a bucket is full the shallowest entry is replaced, preferring entries left over
+/// from earlier searches. Probing and storing only need a shared reference, so all
+/// threads of a search use the same table.
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    age: u8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let mut tt = TranspositionTable {
+            buckets: Vec::new(),
+            age: 0,
+        };
+        tt.resize(size_mb);
+        return tt;
+    }
+
+    /// Reallocates the table to use `size_mb` megabytes, dropping all entries.
+    pub fn resize(&mut self, size_mb: usize) {
+        let size_mb = size_mb.clamp(1, MAX_SIZE_MB);
+        let num_buckets = size_mb * 1024 * 1024 / mem::size_of::<Bucket>();
+        self.buckets = (0..num_buckets).map(|_| Bucket::new()).collect();
+        self.age = 0;
+    }
+
+    pub fn clear(&mut self) {
+        for bucket in self.buckets.iter() {
+            for slot in bucket.entries.iter() {
+                slot.clear();
+            }
+        }
+        self.age = 0;
+    }
+
+    /// Marks the start of a new search, entries from older searches get replaced first.
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    fn bucket_index(&self, key: u64) -> usize {
+        return ((key as u128 * self.buckets.len() as u128) >> 64) as usize;
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TransitionEntry> {
+        let bucket = &self.buckets[self.bucket_index(key)];
+        return bucket
+            .entries
+            .iter()
+            .map(Slot::load)
+            .find(|entry| entry.key == key && !entry.is_empty());
+    }
+
+    pub fn store(&self, key: u64, value: Score, mv: BitMove, depth: u8, move_type: MoveType) {
+        let age = self.age;
+        let bucket = &self.buckets[self.bucket_index(key)];
+
+        let mut replace = 0;
+        let mut old = TransitionEntry::empty();
+        let mut worst = i32::MAX;
+        for (i, slot) in bucket.entries.iter().enumerate() {
+            let entry = slot.load();
+            if entry.key == key {
+                replace = i;
+                old = entry;
+                break;
+            }
+            // every search an entry is old counts as much as 4 plies of depth
+            let stale = age.wrapping_sub(entry.age) as i32;
+            let worth = if entry.is_empty() {
+                i32::MIN
+            } else {
+                entry.depth as i32 - 4 * stale
+            };
+            if worth < worst {
+                worst = worth;
+                replace = i;
+            }
+        }
+
+        // keep a deeper result for the same position unless it is from an older search,
+        // even against an exact score, e.g. a depth 0 one from the quiescence search
+        if old.key == key && old.age == age && old.depth > depth {
+            return;
+        }
+        bucket.entries[replace].save(&TransitionEntry {
+            key,
+            value,
+            mv,
+            depth,
+            move_type,
+            age,
+        });
+    }
+
+    /// Permille of the table used by the current search, sampled from the first buckets.
+    pub fn hashfull(&self) -> usize {
+        let sample = self.buckets.iter().take(1000);
+        let mut used = 0;
+        let mut total = 0;
+        for bucket in sample {
+            for slot in bucket.entries.iter() {
+                let entry = slot.load();
+                total += 1;
+                if !entry.is_empty() && entry.age == self.age {
+                    used += 1;
+                }
+            }
+        }
+        return used * 1000 / total.max(1);
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> TranspositionTable {
+        return TranspositionTable::new(DEFAULT_SIZE_MB);
+    }
+}
+
+/// pleco 0.5 never hashes the black king, so positions that only differ in its square
+/// share a zobrist key. Mix the square back in before using the key for the table.
+pub fn position_key(board: &Board) -> u64 {
+    let sq = board.king_sq(Player::Black).0 as u64;
+    let mut z = (sq + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    return board.zobrist() ^ z ^ (z >> 31);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score;
+
+    fn some_move() -> BitMove {
+        let board = Board::start_pos();
+        return board.generate_moves()[0];
+    }
+
+    #[test]
+    fn black_king_is_hashed() {
+        let a8 = Board::from_fen("k7/4R3/8/8/8/8/8/3KR3 b - - 3 2").unwrap();
+        let b8 = Board::from_fen("1k6/4R3/8/8/8/8/8/3KR3 b - - 3 2").unwrap();
+        assert_ne!(position_key(&a8), position_key(&b8));
+    }
+
+    #[test]
+    fn size() {
+        let tt = TranspositionTable::new(1);
+        let bytes = tt.buckets.len() * mem::size_of::<Bucket>();
+        assert!(bytes <= 1024 * 1024);
+        assert!(bytes > 1024 * 1024 / 2);
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let mut tt = TranspositionTable::new(1);
+        let mv = some_move();
+        assert!(tt.probe(42).is_none());
+
+        tt.store(42, 150, mv, 3, MoveType::Exact);
+        let entry = tt.probe(42).unwrap();
+        assert_eq!(entry.value, 150);
+        assert_eq!(entry.mv, mv);
+        assert_eq!(entry.depth, 3);
+        assert_eq!(entry.move_type, MoveType::Exact);
+
+        tt.clear();
+        assert!(tt.probe(42).is_none());
+    }
+
+    #[test]
+    fn packing() {
+        let tt = TranspositionTable::new(1);
+        let mv = some_move();
+        for (value, move_type) in [
+            (-score::MATE, MoveType::UpperBound),
+            (score::INFINITE, MoveType::LowerBound),
+            (-1, MoveType::Exact),
+        ] {
+            tt.store(7, value, mv, 255, move_type);
+            let entry = tt.probe(7).unwrap();
+            assert_eq!(entry.value, value);
+            assert_eq!(entry.mv, mv);
+            assert_eq!(entry.depth, 255);
+            assert_eq!(entry.move_type, move_type);
+        }
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let tt = TranspositionTable::new(1);
+        std::thread::scope(|scope| {
+            for thread in 0..4u64 {
+                let tt = &tt;
+                scope.spawn(move || {
+                    for key in 1..2000u64 {
+                        let depth = (key % 50) as u8;
+                        tt.store(
+                            key * 4 + thread,
+                            key as Score,
+                            BitMove::null(),
+                            depth,
+                            MoveType::Exact,
+                        );
+                    }
+                });
+            }
+        });
+        // whatever survived the races is intact
+        for key in 4..8000u64 {
+            if let Some(entry) = tt.probe(key) {
+                assert_eq!(entry.value, (key / 4) as Score);
+                assert_eq!(entry.depth, (key / 4 % 50) as u8);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_deeper_entry() {
+        let mut tt = TranspositionTable::new(1);
+        tt.store(42, 100, BitMove::null(), 6, MoveType::LowerBound);
+        tt.store(42, 200, BitMove::null(), 2, MoveType::UpperBound);
+        assert_eq!(tt.probe(42).unwrap().depth, 6);
+        tt.store(42, 50, BitMove::null(), 0, MoveType::Exact);
+        assert_eq!(tt.probe(42).unwrap().depth, 6);
+        tt.store(42, 50, BitMove::null(), 6, MoveType::Exact);
+        assert_eq!(tt.probe(42).unwrap().move_type, MoveType::Exact);
+
+        tt.new_search();
+        tt.store(42, 200, BitMove::null(), 2, MoveType::UpperBound);
+        assert_eq!(tt.probe(42).unwrap().depth, 2);
+    }
+
+    #[test]
+    fn replaces_shallowest_in_full_bucket() {
+        let tt = TranspositionTable::new(1);
+        // keys with the same high bits end up in the same bucket
+        let keys: Vec<u64> = (1..=ENTRIES_PER_BUCKET as u64 + 1).collect();
+        for (i, key) in keys.iter().take(ENTRIES_PER_BUCKET).enumerate() {
+            tt.store(*key, 0, BitMove::null(), 10 - i as u8, MoveType::Exact);
+        }
+        let shallowest = keys[ENTRIES_PER_BUCKET - 1];
+        let new_key = keys[ENTRIES_PER_BUCKET];
+        tt.store(new_key, 0, BitMove::null(), 8, MoveType::Exact);
+
+        assert!(tt.probe(new_key).is_some());
+        assert!(tt.probe(shallowest).is_none());
+        assert!(tt.probe(keys[0]).is_some());
+    }
+
+    #[test]
+    fn prefers_replacing_old_entries() {
+        let mut tt = TranspositionTable::new(1);
+        let keys: Vec<u64> = (1..=ENTRIES_PER_BUCKET as u64 + 1).collect();
+        tt.store(keys[0], 0, BitMove::null(), 12, MoveType::Exact);
+        for _ in 0..4 {
+            tt.new_search();
+        }
+        for key in keys.iter().take(ENTRIES_PER_BUCKET).skip(1) {
+            tt.store(*key, 0, BitMove::null(), 2, MoveType::Exact);
+        }
+        tt.store(
+            keys[ENTRIES_PER_BUCKET],
+            0,
+            BitMove::null(),
+            2,
+            MoveType::Exact,
+        );
+        assert!(tt.probe(keys[0]).is_none());
+    }
+
+    #[test]
+    fn resize_and_hashfull() {
+        let mut tt = TranspositionTable::new(1);
+        tt.store(42, 0, BitMove::null(), 1, MoveType::Exact);
+        tt.resize(2);
+        assert_eq!(tt.buckets.len(), 2 * 1024 * 1024 / mem::size_of::<Bucket>());
+        assert!(tt.probe(42).is_none());
+        assert_eq!(tt.hashfull(), 0);
+
+        for key in 1..100_000u64 {
+            tt.store(
+                key.wrapping_mul(0x9E37_79B9_7F4A_7C15),
+                0,
+                BitMove::null(),
+                1,
+                MoveType::Exact,
+            );
+        }
+        assert!(tt.hashfull() > 0);
+    }
+}
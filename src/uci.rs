@@ -0,0 +1,378 @@
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::engine::{self, Engine, SharedEngine};
+use crate::position::PositionError;
+use crate::search::{
+    SearchParams, SearchResult, MAX_CONTEMPT, MAX_DEPTH, MAX_THREADS, TUNABLE_PARAMS,
+};
+use crate::timeman::SearchLimits;
+use crate::{bench, score, tt, utils};
+
+const ENGINE_NAME: &str = "rc2d2";
+const ENGINE_AUTHOR: &str = "jacob";
+
+pub struct Uci {
+    engine: Arc<SharedEngine>,
+    /// False after a `position` command that could not be set up, so `go` does not
+    /// search a position the GUI no longer has.
+    has_position: bool,
+    search_thread: Option<JoinHandle<()>>,
+}
+
+impl Uci {
+    pub fn new() -> Uci {
+        return Uci {
+            engine: Arc::new(SharedEngine::new(Engine::new(tt::DEFAULT_SIZE_MB))),
+            has_position: true,
+            search_thread: None,
+        };
+    }
+
+    /// Handles a single line of input. Returns false when the engine should exit.
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            return true;
+        }
+
+        match tokens[0] {
+            "uci" => {
+                println!("id name {}", ENGINE_NAME);
+                println!("id author {}", ENGINE_AUTHOR);
+                println!(
+                    "option name Depth type spin default {} min 1 max {}",
+                    engine::DEFAULT_DEPTH,
+                    MAX_DEPTH
+                );
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    tt::DEFAULT_SIZE_MB,
+                    tt::MAX_SIZE_MB
+                );
+                println!(
+                    "option name Contempt type spin default 0 min {} max {}",
+                    -MAX_CONTEMPT, MAX_CONTEMPT
+                );
+                println!(
+                    "option name Threads type spin default 1 min 1 max {}",
+                    MAX_THREADS
+                );
+                println!("option name QuiescenceChecks type check default false");
+                let defaults = SearchParams::default();
+                for (name, min, max) in TUNABLE_PARAMS.iter() {
+                    println!(
+                        "option name {} type spin default {} min {} max {}",
+                        name,
+                        defaults.tunable(name).unwrap(),
+                        min,
+                        max
+                    );
+                }
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.wait_for_search();
+                self.engine.lock().new_game();
+                self.has_position = true;
+            }
+            "position" => {
+                self.wait_for_search();
+                let result = set_position(&mut self.engine.lock(), &tokens[1..]);
+                self.has_position = result.is_ok();
+                if let Err(err) = result {
+                    println!("info string {}", err);
+                }
+            }
+            "setoption" => self.set_option(&tokens[1..]),
+            "go" => self.go(&tokens[1..]),
+            "stop" => self.stop_search(),
+            "bench" => {
+                self.wait_for_search();
+                let depth = tokens.get(1).and_then(|depth| depth.parse().ok());
+                bench::run(depth.unwrap_or(bench::DEFAULT_DEPTH), true);
+            }
+            "quit" => {
+                self.stop_search();
+                return false;
+            }
+            _ => println!("info string unknown command: {}", tokens[0]),
+        }
+        return true;
+    }
+
+    fn set_option(&mut self, args: &[&str]) {
+        let (name, value) = parse_option(args);
+        // check options are sent as `true` or `false`
+        let number = match value.as_str() {
+            "true" => Some(1),
+            "false" => Some(0),
+            _ => value.parse::<i64>().ok(),
+        };
+        let number = match number {
+            Some(number) => number,
+            None => {
+                println!("info string invalid value for {}: {}", name, value);
+                return;
+            }
+        };
+        self.wait_for_search();
+        if !self.engine.lock().set_option(&name, number) {
+            println!("info string unknown option: {}", name);
+        }
+    }
+
+    fn go(&mut self, args: &[&str]) {
+        self.wait_for_search();
+        if !self.has_position {
+            println!("info string no valid position to search");
+            println!("bestmove 0000");
+            return;
+        }
+        let limits = parse_go(args);
+        let engine = self.engine.clone();
+        // taken here so a `stop` right after `go` reaches the search
+        let stop = engine.stop_flag();
+
+        let handle = thread::spawn(move || {
+            let result = engine.lock().search(&limits, stop, print_info);
+            match result {
+                Ok(result) => print_best_move(&result),
+                Err(_) => println!("bestmove 0000"),
+            }
+        });
+        self.search_thread = Some(handle);
+    }
+
+    fn stop_search(&mut self) {
+        self.engine.stop();
+        self.wait_for_search();
+    }
+
+    fn wait_for_search(&mut self) {
+        if let Some(handle) = self.search_thread.take() {
+            // a panicking search leaves the engine usable, see `SharedEngine::lock`
+            let _ = handle.join();
+        }
+    }
+}
+
+fn print_best_move(result: &SearchResult) {
+    match result.ponder_move() {
+        Some(ponder) => println!(
+            "bestmove {} ponder {}",
+            result.best_move.stringify(),
+            ponder.stringify()
+        ),
+        None => println!("bestmove {}", result.best_move.stringify()),
+    }
+}
+
+fn print_info(result: &SearchResult) {
+    println!(
+        "info depth {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+        result.depth,
+        score::to_uci(result.score),
+        result.nodes,
+        result.nodes * 1000 / (result.elapsed.as_millis() as u64).max(1),
+        result.hashfull,
+        result.elapsed.as_millis(),
+        utils::format_pv(&result.pv)
+    );
+}
+
+/// Sets up the position of a `position` command, e.g.
+/// `startpos moves e2e4 e7e5` or `fen <fen> moves e2e4`.
+fn set_position(engine: &mut Engine, args: &[&str]) -> Result<(), PositionError> {
+    let moves_idx = args
+        .iter()
+        .position(|t| *t == "moves")
+        .unwrap_or(args.len());
+    let moves = args.get(moves_idx + 1..).unwrap_or(&[]);
+    return match args.first() {
+        Some(&"startpos") => engine.set_position(None, moves),
+        Some(&"fen") => engine.set_position(Some(&args[1..moves_idx].join(" ")), moves),
+        _ => Err(PositionError::InvalidFen(args.join(" "))),
+    };
+}
+
+/// Splits `name <name> value <value>` into its parts. Option names may contain spaces.
+fn parse_option(args: &[&str]) -> (String, String) {
+    let value_idx = args
+        .iter()
+        .position(|t| *t == "value")
+        .unwrap_or(args.len());
+    let name = args
+        .iter()
+        .take(value_idx)
+        .skip(1)
+        .cloned()
+        .collect::<Vec<&str>>();
+    let value = args
+        .iter()
+        .skip(value_idx + 1)
+        .cloned()
+        .collect::<Vec<&str>>();
+    return (name.join(" "), value.join(" "));
+}
+
+/// Parses the arguments of a `go` command. Unknown or malformed arguments are ignored.
+fn parse_go(args: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut iter = args.iter();
+    while let Some(token) = iter.next() {
+        if *token == "infinite" {
+            limits.infinite = true;
+            continue;
+        }
+        let value = match *token {
+            "wtime" | "btime" | "winc" | "binc" | "movestogo" | "movetime" | "depth" => {
+                iter.next().and_then(|v| v.parse::<i64>().ok())
+            }
+            _ => continue,
+        };
+        // GUIs may send negative times when a player is already out of time.
+        let value = match value {
+            Some(value) => value.max(0) as u64,
+            None => continue,
+        };
+        match *token {
+            "wtime" => limits.wtime = Some(value),
+            "btime" => limits.btime = Some(value),
+            "winc" => limits.winc = Some(value),
+            "binc" => limits.binc = Some(value),
+            "movestogo" => limits.movestogo = Some(value),
+            "movetime" => limits.movetime = Some(value),
+            "depth" => limits.depth = Some(value.clamp(1, MAX_DEPTH as u64) as u8),
+            _ => {}
+        }
+    }
+    return limits;
+}
+
+pub fn run() {
+    let mut uci = Uci::new();
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !uci.handle_command(&line) {
+            break;
+        }
+    }
+    uci.wait_for_search();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pleco::{Board, Player};
+
+    #[test]
+    fn position_startpos() {
+        let mut engine = Engine::new(1);
+        assert!(set_position(&mut engine, &["startpos", "moves", "e2e4"]).is_ok());
+        assert!(set_position(&mut engine, &["startpos"]).is_ok());
+        assert_eq!(engine.board().fen(), Board::start_pos().fen());
+    }
+
+    #[test]
+    fn position_startpos_moves() {
+        let mut engine = Engine::new(1);
+        let args = ["startpos", "moves", "e2e4", "e7e5", "g1f3"];
+        assert!(set_position(&mut engine, &args).is_ok());
+        assert_eq!(
+            engine.board().fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+    }
+
+    #[test]
+    fn position_fen_moves() {
+        let args: Vec<&str> = "fen k7/5R2/6R1/8/8/8/4K3/8 w - - 0 1 moves g6g8"
+            .split(' ')
+            .collect();
+        let mut engine = Engine::new(1);
+        assert!(set_position(&mut engine, &args).is_ok());
+        assert!(engine.board().checkmate());
+    }
+
+    #[test]
+    fn position_illegal_move() {
+        let mut engine = Engine::new(1);
+        assert_eq!(
+            set_position(&mut engine, &["startpos", "moves", "e2e5"]),
+            Err(PositionError::IllegalMove("e2e5".to_string()))
+        );
+        assert!(set_position(&mut engine, &["fen", "not", "a", "fen"]).is_err());
+        assert!(set_position(&mut engine, &[]).is_err());
+    }
+
+    #[test]
+    fn invalid_position_clears_board() {
+        let mut uci = Uci::new();
+        assert!(uci.handle_command("position startpos moves e2e4"));
+        assert!(uci.has_position);
+        assert!(uci.handle_command("position fen 8/8/8/8/8/8/8/8 w - - 0 1"));
+        assert!(!uci.has_position);
+        // answered with a null move instead of searching the previous position
+        assert!(uci.handle_command("go depth 1"));
+        assert!(uci.search_thread.is_none());
+        assert!(uci.handle_command("ucinewgame"));
+        assert!(uci.has_position);
+    }
+
+    #[test]
+    fn options() {
+        let mut uci = Uci::new();
+        assert!(uci.handle_command("setoption name Depth value 6"));
+        assert!(uci.handle_command("setoption name QuiescenceChecks value true"));
+        assert!(uci.handle_command("setoption name Threads value 1000"));
+        assert!(uci.handle_command("setoption name Contempt value x"));
+        let engine = uci.engine.lock();
+        assert_eq!(engine.depth, 6);
+        assert!(engine.params.quiescence_checks);
+        assert_eq!(engine.params.threads, MAX_THREADS);
+        assert_eq!(engine.params.contempt, 0);
+    }
+
+    #[test]
+    fn option_parsing() {
+        let (name, value) = parse_option(&["name", "Depth", "value", "6"]);
+        assert_eq!(name, "Depth");
+        assert_eq!(value, "6");
+    }
+
+    #[test]
+    fn go_depth() {
+        assert_eq!(parse_go(&["depth", "5"]), SearchLimits::depth(5));
+        assert_eq!(parse_go(&["wtime", "1000"]).depth, None);
+    }
+
+    #[test]
+    fn go_clock() {
+        let args: Vec<&str> = "wtime 60000 btime -20 winc 1000 binc 1000 movestogo 20"
+            .split(' ')
+            .collect();
+        let limits = parse_go(&args);
+        assert_eq!(limits.wtime, Some(60000));
+        assert_eq!(limits.btime, Some(0));
+        assert_eq!(limits.winc, Some(1000));
+        assert_eq!(limits.binc, Some(1000));
+        assert_eq!(limits.movestogo, Some(20));
+        assert!(limits.is_timed(Player::White));
+        assert!(limits.is_timed(Player::Black));
+        assert_eq!(parse_go(&["movetime", "500"]).movetime, Some(500));
+    }
+
+    #[test]
+    fn go_infinite() {
+        let limits = parse_go(&["infinite"]);
+        assert!(limits.infinite);
+        assert!(!limits.is_timed(Player::White));
+    }
+}
@@ -1,26 +1,26 @@
+use crate::score::{self, Score};
+use crate::tt::TranspositionTable;
 use crate::{eval, search};
-use pleco::{Board, Player};
-use std::collections::HashMap;
+use pleco::{BitMove, Board, Player};
 pub fn play_x_moves(
     mut board: Board,
     num_moves: u8,
     depth: u8,
     start_color: i8,
-    evaluator: fn(&Board) -> f32,
+    evaluator: fn(&Board) -> Score,
 ) -> Board {
     let mut color: i8 = start_color;
     for i in 0..num_moves {
-        let mut tt: HashMap<u64, search::TransitionEntry> = HashMap::new();
+        let mut tt = TranspositionTable::default();
 
         let (score, mv) = search::nega_max(
             board.shallow_clone(),
             depth,
             color,
-            -9999.0,
-            9999.0,
-            &mut tt,
-            true,
-            evaluator,
+            -score::INFINITE,
+            score::INFINITE,
+            &mut search::SearchInfo::new(&tt, evaluator),
+            0,
             true,
         );
         board.apply_move(mv);
@@ -33,8 +33,8 @@ pub fn play_match(depth: u8) {
     let mut board = Board::start_pos();
     let mut color = 1;
     let mut count = 0;
-    let mut black_tt: HashMap<u64, search::TransitionEntry> = HashMap::new();
-    let mut white_tt: HashMap<u64, search::TransitionEntry> = HashMap::new();
+    let mut black_tt = TranspositionTable::default();
+    let mut white_tt = TranspositionTable::default();
     while !board.generate_moves().is_empty() {
         println!("{}", board);
         count += 1;
@@ -47,44 +47,28 @@ pub fn play_match(depth: u8) {
             board.shallow_clone(),
             depth,
             color,
-            -10000.0,
-            10000.0,
-            transition_table,
-            true,
-            eval::eval,
+            -score::INFINITE,
+            score::INFINITE,
+            &mut search::SearchInfo::new(transition_table, eval::eval),
+            0,
             true,
         );
         color = -color;
-        let size = transition_table.keys().len();
         board.apply_move(mv);
     }
 }
 
-pub fn find_best_move(uci_moves: &str, depth: u8) -> String {
-    let moves: Vec<&str> = uci_moves.split(" ").collect();
-    let mut board = Board::start_pos();
-    let num_moves = moves.len();
-    for mv in moves {
-        board.apply_uci_move(mv);
-    }
-
-    let color = match board.turn() {
-        Player::White => 1,
-        Player::Black => -1,
-    };
-    let mut tt: HashMap<u64, search::TransitionEntry> = HashMap::new();
+pub fn format_pv(pv: &[BitMove]) -> String {
+    let moves: Vec<String> = pv.iter().map(|mv| mv.stringify()).collect();
+    return moves.join(" ");
+}
 
-    let (score, mv) = search::nega_max(
-        board.shallow_clone(),
-        depth,
-        color,
-        -9999.0,
-        9999.0,
-        &mut tt,
-        true,
-        eval::eval,
-        true,
-    );
-    println!("{}. move: {}, score: {}", num_moves + 1, mv.stringify(), score);
-    return mv.stringify();
+/// The legal move written as `uci` in `board`, panics if there is none.
+#[cfg(test)]
+pub fn find_move(board: &Board, uci: &str) -> BitMove {
+    return *board
+        .generate_moves()
+        .iter()
+        .find(|mv| mv.stringify() == uci)
+        .unwrap();
 }